@@ -1,24 +1,501 @@
+use crate::config::{self, Config, Origin};
+use crate::i18n::{self, Messages};
+use crate::template::{self, TemplateError};
+use crate::{git, naming};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
-fn claude_available() -> bool {
+/// Seconds to wait for an HTTP backend to answer
+const HTTP_TIMEOUT: u64 = 60;
+
+const ANTHROPIC_URL: &str = "https://api.anthropic.com";
+/// Model for the Anthropic API when `model` isn't set
+const DEFAULT_CLAUDE_MODEL: &str = "claude-haiku-4-5-20251001";
+/// Model for the OpenAI backend when `model` isn't set
+const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+/// Model for the Ollama backend when `model` isn't set
+const DEFAULT_OLLAMA_MODEL: &str = "llama3.2";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const OPENAI_URL: &str = "https://api.openai.com/v1";
+const OLLAMA_URL: &str = "http://localhost:11434";
+
+/// Values for `ai.backend`, as shown in settings
+pub const BACKENDS: [(&str, &str); 4] = [
+    ("claude-cli", "Claude Code CLI  (claude -p)"),
+    ("anthropic", "Anthropic API    (ANTHROPIC_API_KEY)"),
+    ("openai", "OpenAI-compatible (OPENAI_API_KEY, ai.url)"),
+    ("ollama", "Ollama           (localhost:11434)"),
+];
+
+/// Something that can answer a branch naming prompt
+pub trait BranchNamer {
+    /// The model's reply to `prompt`, or why there is none
+    fn complete(&self, prompt: &str) -> Result<String, String>;
+}
+
+/// The `claude` CLI, signed in on its own
+pub struct ClaudeCli {
+    pub model: Option<String>,
+}
+
+/// The Anthropic Messages API
+pub struct Anthropic {
+    pub url: String,
+    pub api_key: String,
+    pub model: String,
+}
+
+/// Any server with OpenAI's chat completions endpoint
+pub struct OpenAi {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+}
+
+/// A local Ollama server
+pub struct Ollama {
+    pub url: String,
+    pub model: String,
+}
+
+impl BranchNamer for ClaudeCli {
+    fn complete(&self, prompt: &str) -> Result<String, String> {
+        let mut cmd = Command::new("claude");
+        if let Some(model) = &self.model {
+            cmd.args(["--model", model]);
+        }
+        cmd.args(["-p", prompt]);
+        cmd.env("CLAUDECODE", "");
+        cmd.stderr(Stdio::null());
+
+        let output = cmd.output().map_err(|e| format!("claude: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("claude: {}", output.status));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl BranchNamer for Anthropic {
+    fn complete(&self, prompt: &str) -> Result<String, String> {
+        let reply = post_json(
+            &format!("{}/v1/messages", self.url.trim_end_matches('/')),
+            &[
+                ("x-api-key", &self.api_key),
+                ("anthropic-version", ANTHROPIC_VERSION),
+            ],
+            &json!({
+                "model": self.model,
+                "max_tokens": 256,
+                "messages": [{"role": "user", "content": prompt}],
+            }),
+        )?;
+        text_at(&reply, "/content/0/text")
+    }
+}
+
+impl BranchNamer for OpenAi {
+    fn complete(&self, prompt: &str) -> Result<String, String> {
+        let auth = self.api_key.as_ref().map(|key| format!("Bearer {}", key));
+        let headers: Vec<(&str, &str)> = auth
+            .iter()
+            .map(|value| ("authorization", value.as_str()))
+            .collect();
+        let reply = post_json(
+            &format!("{}/chat/completions", self.url.trim_end_matches('/')),
+            &headers,
+            &json!({
+                "model": self.model,
+                "messages": [{"role": "user", "content": prompt}],
+            }),
+        )?;
+        text_at(&reply, "/choices/0/message/content")
+    }
+}
+
+impl BranchNamer for Ollama {
+    fn complete(&self, prompt: &str) -> Result<String, String> {
+        let reply = post_json(
+            &format!("{}/api/generate", self.url.trim_end_matches('/')),
+            &[],
+            &json!({"model": self.model, "prompt": prompt, "stream": false}),
+        )?;
+        text_at(&reply, "/response")
+    }
+}
+
+fn text_at(reply: &Value, pointer: &str) -> Result<String, String> {
+    reply
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| "unexpected response".to_string())
+}
+
+/// A string for a curl config file
+fn curl_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// POSTs `body` with curl, which already handles TLS and proxies. Everything
+/// goes through stdin so API keys don't show up in the process list.
+fn post_json(url: &str, headers: &[(&str, &str)], body: &Value) -> Result<Value, String> {
+    let mut config = format!("url = {}\n", curl_quote(url));
+    for (name, value) in headers {
+        config.push_str(&format!(
+            "header = {}\n",
+            curl_quote(&format!("{}: {}", name, value))
+        ));
+    }
+    config.push_str("header = \"content-type: application/json\"\n");
+    config.push_str("header = \"Expect:\"\n");
+    config.push_str(&format!(
+        "data-binary = {}\n",
+        curl_quote(&body.to_string())
+    ));
+
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--config", "-"])
+        .args(["--max-time", &HTTP_TIMEOUT.to_string()])
+        .args(["--write-out", "\n%{http_code}"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(|e| format!("curl: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    let reply: Option<Value> = serde_json::from_str(body).ok();
+    if status.starts_with('2') {
+        return reply.ok_or_else(|| "unexpected response".to_string());
+    }
+    // Anthropic and OpenAI nest the message, Ollama doesn't
+    let message = reply.as_ref().and_then(|r| {
+        r.pointer("/error/message")
+            .or_else(|| r.get("error"))
+            .and_then(Value::as_str)
+            .map(String::from)
+    });
+    Err(message.unwrap_or_else(|| format!("HTTP {}", status)))
+}
+
+/// The backend chosen by `ai.backend`, or None if it can't be used. A
+/// missing API key or `curl` is reported; a missing `claude` CLI is not,
+/// since that's the default.
+fn namer(config: &Config, m: &Messages) -> Option<Box<dyn BranchNamer>> {
+    let backend = config.get("ai.backend").unwrap_or("claude-cli");
+    let url = config.get("ai.url").map(String::from);
+    let model = config.get("model").map(String::from);
+    let api_key = |var: &str| {
+        config
+            .get("ai.api_key")
+            .map(String::from)
+            .or_else(|| std::env::var(var).ok().filter(|k| !k.is_empty()))
+    };
+
+    match backend {
+        "anthropic" | "openai" | "ollama" if !on_path("curl") => {
+            crate::err(&m.ai_no_curl.replacen("%s", backend, 1));
+            None
+        }
+        "anthropic" => match api_key("ANTHROPIC_API_KEY") {
+            Some(api_key) => Some(Box::new(Anthropic {
+                url: url.unwrap_or_else(|| ANTHROPIC_URL.to_string()),
+                api_key,
+                model: model.unwrap_or_else(|| DEFAULT_CLAUDE_MODEL.to_string()),
+            })),
+            None => {
+                crate::err(&m.ai_no_api_key.replacen("%s", backend, 1).replacen(
+                    "%s",
+                    "ANTHROPIC_API_KEY",
+                    1,
+                ));
+                None
+            }
+        },
+        "openai" => Some(Box::new(OpenAi {
+            url: url.unwrap_or_else(|| OPENAI_URL.to_string()),
+            api_key: api_key("OPENAI_API_KEY"),
+            model: model.unwrap_or_else(|| DEFAULT_OPENAI_MODEL.to_string()),
+        })),
+        "ollama" => Some(Box::new(Ollama {
+            url: url.unwrap_or_else(|| OLLAMA_URL.to_string()),
+            model: model.unwrap_or_else(|| DEFAULT_OLLAMA_MODEL.to_string()),
+        })),
+        _ if on_path("claude") => Some(Box::new(ClaudeCli { model })),
+        _ => None,
+    }
+}
+
+fn on_path(program: &str) -> bool {
     std::env::var_os("PATH")
-        .map(|path| std::env::split_paths(&path).any(|dir| dir.join("claude").exists()))
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).exists()))
         .unwrap_or(false)
 }
 
-fn build_prompt(description: &str, feedback: &str) -> String {
-    let mut prompt = format!(
-        "Suggest exactly one git branch name for the following implementation.\n\
-         Rules:\n\
+/// Most branch names the AI may be asked for at once (`ai.candidates`)
+pub const MAX_CANDIDATES: u64 = 5;
+
+/// Placeholders a prompt template (`ai.prompt_template`) may use
+pub const PROMPT_PLACEHOLDERS: &[&str] = &[
+    "description",
+    "feedback",
+    "existing_branches",
+    "lang",
+    "count",
+];
+/// Without these a template would ignore what the user asked for
+const REQUIRED_PLACEHOLDERS: &[&str] = &["description", "feedback"];
+
+/// Branch names shown to the model as examples of house style
+const SAMPLE_BRANCHES: usize = 15;
+/// Branch names looked at to detect common prefixes
+const SCANNED_BRANCHES: usize = 200;
+
+/// What the prompt tells the model about the repository
+#[derive(Debug, Default)]
+pub struct RepoContext {
+    /// Recently used branch names, newest first
+    pub recent_branches: Vec<String>,
+    /// Prefixes shared by several branches, with how many use each
+    pub prefixes: Vec<(String, usize)>,
+    /// Prefixes new branches must use (`branch.prefixes`)
+    pub allowed_prefixes: Vec<String>,
+    /// `branch.max_length`
+    pub max_length: Option<usize>,
+    /// The repository's own naming rules (`ai.instructions`)
+    pub instructions: Option<String>,
+    /// Code of the interface language, such as `ja`
+    pub lang: String,
+    /// A checked `ai.prompt_template` to use instead of the built-in prompt
+    pub template: Option<String>,
+}
+
+impl RepoContext {
+    pub fn load(config: &Config, m: &Messages) -> RepoContext {
+        let branches = git::recent_branch_names(SCANNED_BRANCHES);
+        let rules = naming::Rules::load(config);
+        let lang = config::resolve_lang(config);
+        RepoContext {
+            prefixes: detect_prefixes(&branches),
+            recent_branches: branches.into_iter().take(SAMPLE_BRANCHES).collect(),
+            allowed_prefixes: rules.prefixes,
+            max_length: rules.max_length,
+            instructions: config.get("ai.instructions").map(String::from),
+            lang: i18n::LANG_CODES
+                .iter()
+                .find(|(_, l)| *l == lang)
+                .map(|(code, _)| code.to_string())
+                .unwrap_or_default(),
+            template: load_template(config, m),
+        }
+    }
+}
+
+/// Where `ai.prompt_template` points: `~/` is `home` and a relative path
+/// starts at `top`, the top of the repository. A template set by the
+/// repository must stay inside it, so a cloned `.shuu.toml` can't put other
+/// files into the prompt.
+fn template_path(
+    value: &str,
+    origin: Origin,
+    top: Option<&Path>,
+    home: &Path,
+    m: &Messages,
+) -> Result<PathBuf, String> {
+    let path = match value.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None => match top {
+            Some(top) => top.join(value),
+            None => PathBuf::from(value),
+        },
+    };
+    let path = path.canonicalize().map_err(|e| e.to_string())?;
+
+    if origin == Origin::Repo {
+        let inside = top
+            .and_then(|top| top.canonicalize().ok())
+            .is_some_and(|top| path.starts_with(top));
+        if !inside {
+            return Err(m.prompt_template_outside.to_string());
+        }
+    }
+    Ok(path)
+}
+
+/// The `ai.prompt_template` file, if it is set, readable and valid. Problems
+/// are reported and leave the built-in prompt in place.
+fn load_template(config: &Config, m: &Messages) -> Option<String> {
+    let entry = config.entry("ai.prompt_template")?;
+    let top = git::toplevel().map(PathBuf::from);
+    let home = dirs::home_dir().unwrap_or_default();
+    let result = template_path(&entry.value, entry.origin, top.as_deref(), &home, m)
+        .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+        .and_then(|text| {
+            template::check(&text, PROMPT_PLACEHOLDERS, REQUIRED_PLACEHOLDERS)
+                .map(|_| text)
+                .map_err(|e| e.describe(m))
+        });
+
+    match result {
+        Ok(text) => Some(text),
+        Err(reason) => {
+            crate::err(
+                &m.prompt_template_ignored
+                    .replacen("%s", &entry.value, 1)
+                    .replacen("%s", &reason, 1),
+            );
+            None
+        }
+    }
+}
+
+/// `template` with the request and repository filled in
+fn render_template(
+    template: &str,
+    description: &str,
+    feedback: &str,
+    ctx: &RepoContext,
+    count: usize,
+) -> Result<String, TemplateError> {
+    template::render(template, |name| {
+        let value = match name {
+            "description" => description.to_string(),
+            "feedback" => feedback.to_string(),
+            "existing_branches" => ctx
+                .recent_branches
+                .iter()
+                .map(|b| format!("- {}", b))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "lang" => ctx.lang.clone(),
+            "count" => count.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    })
+}
+
+/// First path components used by at least two branches, most common first.
+/// Numbers are generalized so `PROJ-123/x` and `PROJ-456/y` share
+/// `PROJ-{number}/`.
+fn detect_prefixes(branches: &[String]) -> Vec<(String, usize)> {
+    const MAX_PREFIXES: usize = 5;
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for branch in branches {
+        let first = match branch.split_once('/') {
+            Some((first, _)) if !first.is_empty() => first,
+            _ => continue,
+        };
+        let mut prefix = String::new();
+        let mut in_number = false;
+        for c in first.chars() {
+            if c.is_ascii_digit() {
+                if !in_number {
+                    prefix.push_str("{number}");
+                }
+                in_number = true;
+            } else {
+                prefix.push(c);
+                in_number = false;
+            }
+        }
+        prefix.push('/');
+
+        match counts.iter_mut().find(|(p, _)| *p == prefix) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((prefix, 1)),
+        }
+    }
+
+    counts.retain(|(_, n)| *n >= 2);
+    // Stable, so equally common prefixes keep the order they were first seen in
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    counts.truncate(MAX_PREFIXES);
+    counts
+}
+
+/// Prompt asking for `count` names, from the configured template or else
+/// following the repository's conventions where `ctx` knows them
+fn build_prompt(description: &str, feedback: &str, ctx: &RepoContext, count: usize) -> String {
+    if let Some(template) = &ctx.template {
+        if let Ok(prompt) = render_template(template, description, feedback, ctx, count) {
+            return prompt;
+        }
+    }
+
+    let mut prompt = if count > 1 {
+        format!(
+            "Suggest {} different git branch names for the following implementation.\n",
+            count
+        )
+    } else {
+        String::from("Suggest exactly one git branch name for the following implementation.\n")
+    };
+    prompt.push_str(
+        "Rules:\n\
          - kebab-case\n\
          - English\n\
-         - Short and concise (2-4 words)\n\
-         - Use prefix: feat/, fix/, refactor/, etc.\n\
-         - Output only the branch name (no explanation)\n\
-         \n\
-         Implementation: {}",
-        description
+         - Short and concise (2-4 words)\n",
     );
+    if !ctx.allowed_prefixes.is_empty() {
+        prompt.push_str(&format!(
+            "- Use one of these prefixes: {}\n",
+            ctx.allowed_prefixes.join(", ")
+        ));
+    } else if !ctx.prefixes.is_empty() {
+        prompt.push_str("- Use the same kind of prefix as the repository's existing branches\n");
+    } else {
+        prompt.push_str("- Use prefix: feat/, fix/, refactor/, etc.\n");
+    }
+    if let Some(max) = ctx.max_length {
+        prompt.push_str(&format!("- At most {} characters\n", max));
+    }
+    if count > 1 {
+        prompt.push_str(
+            "- Output only a JSON array of the names, e.g. [\"feat/add-login\", \"feat/login-page\"]\n",
+        );
+    } else {
+        prompt.push_str("- Output only the branch name (no explanation)\n");
+    }
+
+    if let Some(instructions) = &ctx.instructions {
+        prompt.push_str(&format!("- {}\n", instructions.trim()));
+    }
+    if !ctx.prefixes.is_empty() {
+        let prefixes: Vec<String> = ctx
+            .prefixes
+            .iter()
+            .map(|(p, n)| format!("{} ({} branches)", p, n))
+            .collect();
+        prompt.push_str(&format!(
+            "\nPrefixes used in this repository: {}\n",
+            prefixes.join(", ")
+        ));
+    }
+    if !ctx.recent_branches.is_empty() {
+        prompt.push_str("\nRecent branches in this repository:\n");
+        for branch in &ctx.recent_branches {
+            prompt.push_str(&format!("- {}\n", branch));
+        }
+    }
+    prompt.push_str(&format!("\nImplementation: {}", description));
 
     if !feedback.is_empty() {
         prompt.push_str(&format!(
@@ -30,7 +507,83 @@ fn build_prompt(description: &str, feedback: &str) -> String {
     prompt
 }
 
-fn fallback_branch_name(description: &str) -> String {
+/// The prompt `generate_branch_names` would send
+pub fn branch_prompt(
+    config: &Config,
+    description: &str,
+    feedback: &str,
+    count: usize,
+    m: &Messages,
+) -> String {
+    build_prompt(description, feedback, &RepoContext::load(config, m), count)
+}
+
+/// Strings of the first JSON array in `reply`, which may be wrapped in an
+/// object, a code fence or some chatter
+fn json_names(reply: &str) -> Option<Vec<String>> {
+    let value: serde_json::Value = match serde_json::from_str(reply.trim()) {
+        Ok(v) => v,
+        Err(_) => {
+            let start = reply.find('[')?;
+            let end = reply.rfind(']')?;
+            serde_json::from_str(reply.get(start..=end)?).ok()?
+        }
+    };
+    let array = match &value {
+        Value::Array(a) => a,
+        Value::Object(o) => o.values().find_map(Value::as_array)?,
+        _ => return None,
+    };
+    Some(
+        array
+            .iter()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// `line` without a leading `-`, `*`, `1.` or `1)`
+fn strip_list_marker(line: &str) -> &str {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix(['-', '*']) {
+        return rest.trim_start();
+    }
+    let number = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    match number.strip_prefix(['.', ')']) {
+        Some(rest) if number.len() < line.len() => rest.trim_start(),
+        _ => line,
+    }
+}
+
+/// Branch names from a reply, as a JSON array or else one per line without
+/// list markers and wrappers. Lines with spaces but no `/` are taken for
+/// explanations and skipped; the rest are left for `naming` to correct. At
+/// most `count`, without duplicates.
+fn parse_names(reply: &str, count: usize) -> Vec<String> {
+    let raw = json_names(reply).unwrap_or_else(|| {
+        reply
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("```"))
+            .map(|line| strip_list_marker(line).to_string())
+            .collect()
+    });
+
+    let mut names: Vec<String> = Vec::new();
+    for name in raw {
+        let name = naming::strip_wrappers(name.trim().trim_end_matches(','));
+        if name.is_empty() || (name.contains(char::is_whitespace) && !name.contains('/')) {
+            continue;
+        }
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.truncate(count);
+    names
+}
+
+pub fn fallback_branch_name(description: &str) -> String {
     let kebab: String = description
         .to_lowercase()
         .chars()
@@ -45,66 +598,373 @@ fn fallback_branch_name(description: &str) -> String {
     format!("feat/{}", kebab.trim_end_matches('-'))
 }
 
-pub fn generate_branch_name(
+/// Up to `count` suggestions from the configured backend, or the name
+/// derived from the description if there are none
+pub fn generate_branch_names(
+    config: &Config,
     description: &str,
     feedback: &str,
-    messages: &crate::i18n::Messages,
-) -> String {
-    if claude_available() {
+    count: usize,
+    messages: &Messages,
+) -> Vec<String> {
+    if let Some(namer) = namer(config, messages) {
         crate::info(messages.generating_branch);
 
-        let prompt = build_prompt(description, feedback);
-        let mut cmd = Command::new("claude");
-        if let Some(model) = crate::config::get_model() {
-            cmd.args(["--model", &model]);
-        }
-        cmd.args(["-p", &prompt]);
-        cmd.env("CLAUDECODE", "");
-        cmd.stderr(Stdio::null());
-
-        if let Ok(output) = cmd.output() {
-            if output.status.success() {
-                let name = String::from_utf8_lossy(&output.stdout)
-                    .lines()
-                    .next()
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
-                if !name.is_empty() {
-                    return name;
+        let prompt = branch_prompt(config, description, feedback, count, messages);
+        match namer.complete(&prompt) {
+            Ok(reply) => {
+                let names = parse_names(&reply, count);
+                if !names.is_empty() {
+                    return names;
                 }
             }
+            Err(e) => crate::err(&messages.ai_failed.replacen("%s", &e, 1)),
         }
     }
 
-    fallback_branch_name(description)
+    vec![fallback_branch_name(description)]
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    const PROMPT: &str = "Name \"this\" with a \\ and\na newline";
+
+    /// Answers one HTTP request with `status` and `body`, handing back the
+    /// request it got
+    fn mock_server(status: &str, body: &str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| {
+                            let l = l.to_lowercase();
+                            l.strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    fn request_body(request: &str) -> Value {
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn test_anthropic_backend() {
+        let (url, server) = mock_server(
+            "200 OK",
+            r#"{"content":[{"type":"text","text":"feat/login\n"}]}"#,
+        );
+        let namer = Anthropic {
+            url,
+            api_key: "sk-test".to_string(),
+            model: "claude-test".to_string(),
+        };
+        assert_eq!(namer.complete(PROMPT).unwrap(), "feat/login\n");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /v1/messages "));
+        assert!(request.contains("x-api-key: sk-test\r\n"));
+        assert!(request.contains("anthropic-version: 2023-06-01\r\n"));
+        let body = request_body(&request);
+        assert_eq!(body["model"], "claude-test");
+        assert_eq!(body["messages"][0]["content"], PROMPT);
+    }
+
+    #[test]
+    fn test_openai_backend() {
+        let (url, server) = mock_server(
+            "200 OK",
+            r#"{"choices":[{"message":{"role":"assistant","content":"fix/typo"}}]}"#,
+        );
+        let namer = OpenAi {
+            url: format!("{}/v1/", url),
+            api_key: Some("k".to_string()),
+            model: "gpt-test".to_string(),
+        };
+        assert_eq!(namer.complete(PROMPT).unwrap(), "fix/typo");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions "));
+        assert!(request.contains("authorization: Bearer k\r\n"));
+        let body = request_body(&request);
+        assert_eq!(body["model"], "gpt-test");
+        assert_eq!(body["messages"][0]["content"], PROMPT);
+    }
+
+    #[test]
+    fn test_ollama_backend() {
+        let (url, server) = mock_server("200 OK", r#"{"response":"feat/cache","done":true}"#);
+        let namer = Ollama {
+            url,
+            model: "llama-test".to_string(),
+        };
+        assert_eq!(namer.complete(PROMPT).unwrap(), "feat/cache");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /api/generate "));
+        assert!(!request.to_lowercase().contains("authorization"));
+        let body = request_body(&request);
+        assert_eq!(body["prompt"], PROMPT);
+        assert_eq!(body["stream"], false);
+    }
+
+    #[test]
+    fn test_backend_errors() {
+        let (url, server) = mock_server(
+            "401 Unauthorized",
+            r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#,
+        );
+        let namer = Anthropic {
+            url,
+            api_key: "bad".to_string(),
+            model: "m".to_string(),
+        };
+        assert_eq!(namer.complete(PROMPT), Err("invalid x-api-key".to_string()));
+        server.join().unwrap();
+
+        let (url, server) = mock_server("404 Not Found", r#"{"error":"model not found"}"#);
+        let namer = Ollama {
+            url,
+            model: "m".to_string(),
+        };
+        assert_eq!(namer.complete(PROMPT), Err("model not found".to_string()));
+        server.join().unwrap();
+
+        let (url, server) = mock_server("502 Bad Gateway", "oops");
+        let namer = Ollama {
+            url,
+            model: "m".to_string(),
+        };
+        assert_eq!(namer.complete(PROMPT), Err("HTTP 502".to_string()));
+        server.join().unwrap();
+
+        let (url, server) = mock_server("200 OK", r#"{"unexpected":true}"#);
+        let namer = Ollama {
+            url,
+            model: "m".to_string(),
+        };
+        assert_eq!(
+            namer.complete(PROMPT),
+            Err("unexpected response".to_string())
+        );
+        server.join().unwrap();
+    }
 
     #[test]
     fn test_build_prompt_without_feedback() {
-        let prompt = build_prompt("add login feature", "");
+        let prompt = build_prompt("add login feature", "", &RepoContext::default(), 1);
         assert!(prompt.contains("add login feature"));
         assert!(!prompt.contains("Feedback"));
     }
 
     #[test]
     fn test_build_prompt_with_feedback() {
-        let prompt = build_prompt("add login feature", "use oauth instead");
+        let prompt = build_prompt(
+            "add login feature",
+            "use oauth instead",
+            &RepoContext::default(),
+            1,
+        );
         assert!(prompt.contains("add login feature"));
         assert!(prompt.contains("Feedback on previous suggestion: use oauth instead"));
     }
 
     #[test]
     fn test_build_prompt_contains_rules() {
-        let prompt = build_prompt("anything", "");
+        let prompt = build_prompt("anything", "", &RepoContext::default(), 1);
         assert!(prompt.contains("kebab-case"));
         assert!(prompt.contains("feat/, fix/, refactor/"));
     }
 
+    #[test]
+    fn test_build_prompt_with_instructions() {
+        let ctx = RepoContext {
+            instructions: Some("Prefix with the Jira key\n".to_string()),
+            ..RepoContext::default()
+        };
+        let prompt = build_prompt("add login", "", &ctx, 1);
+        assert!(prompt.contains("- Prefix with the Jira key\n\nImplementation: add login"));
+    }
+
+    #[test]
+    fn test_build_prompt_for_several() {
+        let prompt = build_prompt("add login", "", &RepoContext::default(), 4);
+        assert!(prompt.starts_with("Suggest 4 different git branch names"));
+        assert!(prompt.contains("JSON array"));
+        assert!(!prompt.contains("exactly one"));
+    }
+
+    #[test]
+    fn test_build_prompt_with_repo_context() {
+        let ctx = RepoContext {
+            recent_branches: vec!["PROJ-12/fix-login".to_string(), "kaye/cache".to_string()],
+            prefixes: vec![("PROJ-{number}/".to_string(), 7)],
+            max_length: Some(40),
+            ..RepoContext::default()
+        };
+        let prompt = build_prompt("add login", "", &ctx, 1);
+        assert!(prompt
+            .contains("- Use the same kind of prefix as the repository's existing branches\n"));
+        assert!(!prompt.contains("feat/, fix/, refactor/"));
+        assert!(prompt.contains("- At most 40 characters\n"));
+        assert!(prompt.contains("Prefixes used in this repository: PROJ-{number}/ (7 branches)\n"));
+        assert!(prompt.contains(
+            "Recent branches in this repository:\n- PROJ-12/fix-login\n- kaye/cache\n\nImplementation: add login"
+        ));
+
+        let ctx = RepoContext {
+            allowed_prefixes: vec!["feat/".to_string(), "fix/".to_string()],
+            ..ctx
+        };
+        let prompt = build_prompt("add login", "", &ctx, 1);
+        assert!(prompt.contains("- Use one of these prefixes: feat/, fix/\n"));
+    }
+
+    #[test]
+    fn test_build_prompt_from_template() {
+        let ctx = RepoContext {
+            recent_branches: vec!["PROJ-12/fix-login".to_string(), "kaye/cache".to_string()],
+            lang: "ja".to_string(),
+            template: Some(
+                "{count} names ({lang}) for: {description}\n{existing_branches}\nNote: {feedback}"
+                    .to_string(),
+            ),
+            ..RepoContext::default()
+        };
+        assert_eq!(
+            build_prompt("add login", "shorter", &ctx, 3),
+            "3 names (ja) for: add login\n- PROJ-12/fix-login\n- kaye/cache\nNote: shorter"
+        );
+    }
+
+    #[test]
+    fn test_template_path() {
+        let m = i18n::messages(i18n::Lang::En);
+        let dir = std::env::temp_dir().join(format!("shuu-template-{}", std::process::id()));
+        let (top, home) = (dir.join("repo"), dir.join("home"));
+        fs::create_dir_all(top.join("prompts")).unwrap();
+        fs::create_dir_all(&home).unwrap();
+        for file in [
+            top.join("prompts/p.txt"),
+            home.join("p.txt"),
+            dir.join("p.txt"),
+        ] {
+            fs::write(file, "{description} {feedback}").unwrap();
+        }
+        let dir = dir.canonicalize().unwrap();
+        let resolve = |value: &str, origin| template_path(value, origin, Some(&top), &home, m);
+        let outside = Err(m.prompt_template_outside.to_string());
+        let absolute = dir.join("p.txt").display().to_string();
+
+        assert_eq!(
+            resolve("prompts/p.txt", Origin::Repo),
+            Ok(dir.join("repo/prompts/p.txt"))
+        );
+        assert_eq!(resolve("../p.txt", Origin::Repo), outside);
+        assert_eq!(resolve("prompts/../../p.txt", Origin::Repo), outside);
+        assert_eq!(resolve(&absolute, Origin::Repo), outside);
+        assert_eq!(resolve("~/p.txt", Origin::Repo), outside);
+
+        assert_eq!(resolve("~/p.txt", Origin::User), Ok(dir.join("home/p.txt")));
+        assert_eq!(resolve(&absolute, Origin::User), Ok(dir.join("p.txt")));
+        assert!(resolve("missing.txt", Origin::User).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect_prefixes() {
+        let branches: Vec<String> = [
+            "PROJ-123/login",
+            "feat/a",
+            "PROJ-7/cache",
+            "main",
+            "feat/b",
+            "PROJ-45/ui",
+            "kaye/spike",
+        ]
+        .iter()
+        .map(|b| b.to_string())
+        .collect();
+        assert_eq!(
+            detect_prefixes(&branches),
+            vec![("PROJ-{number}/".to_string(), 3), ("feat/".to_string(), 2)]
+        );
+        assert!(detect_prefixes(&["main".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_names_json() {
+        assert_eq!(
+            parse_names(r#"["feat/a", "feat/b", "feat/a", "feat/c"]"#, 5),
+            vec!["feat/a", "feat/b", "feat/c"]
+        );
+        assert_eq!(
+            parse_names("Here you go:\n```json\n[\"fix/x\", \"fix/y\"]\n```", 5),
+            vec!["fix/x", "fix/y"]
+        );
+        assert_eq!(
+            parse_names(r#"{"branches": ["feat/a", "feat/b"]}"#, 1),
+            vec!["feat/a"]
+        );
+    }
+
+    #[test]
+    fn test_parse_names_lines() {
+        assert_eq!(parse_names("feat/login\n", 1), vec!["feat/login"]);
+        assert_eq!(
+            parse_names(
+                "Some options:\n1. `feat/add-login`\n2) feat/login-page\n- \"feat/auth\"\n* feat/sso",
+                5
+            ),
+            vec!["feat/add-login", "feat/login-page", "feat/auth", "feat/sso"]
+        );
+        assert!(parse_names("I cannot help with that.", 3).is_empty());
+        assert_eq!(
+            parse_names("Branch name: `feat/add login`.", 1),
+            vec!["feat/add login"]
+        );
+        assert_eq!(parse_names("feat: add-login", 1), vec!["feat/add-login"]);
+        assert_eq!(
+            parse_names("```text\n2024-cleanup\n```", 3),
+            vec!["2024-cleanup"]
+        );
+    }
+
     #[test]
     fn test_fallback_branch_name_simple() {
         assert_eq!(fallback_branch_name("add login"), "feat/add-login");
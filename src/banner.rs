@@ -1,7 +1,8 @@
 use crate::{config, git, App, BOLD, DIM, GREEN, NC, VERSION, YELLOW};
 
 pub fn show_banner(app: &App) {
-    let model = config::get_model().unwrap_or_else(|| app.messages.model_none.to_string());
+    let model =
+        config::get_model(&app.config).unwrap_or_else(|| app.messages.model_none.to_string());
 
     let (repo_info, branch_info, wt_count) = if git::is_git_repo() {
         let repo = git::repo_name().unwrap_or_else(|| "-".to_string());
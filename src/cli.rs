@@ -0,0 +1,732 @@
+use crate::config::{self, Scope};
+use crate::i18n::Messages;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Interactive,
+    Create(CreateArgs),
+    Checkout {
+        /// Branch query or pull request number
+        target: Option<String>,
+        remote: Option<String>,
+    },
+    List(ListFormat),
+    Remove,
+    Clean(CleanArgs),
+    Switch {
+        /// Worktree to go to: a fuzzy query, `-` for the previous one or
+        /// `main`
+        query: Option<String>,
+    },
+    Settings,
+    Config(ConfigAction),
+    Init {
+        shell: Option<String>,
+    },
+    Help,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CreateArgs {
+    pub description: String,
+    /// Use this branch name instead of generating one
+    pub branch: Option<String>,
+    /// Start point for the new branch
+    pub base: Option<String>,
+    pub no_ai: bool,
+    /// Fetch the base's remote before branching
+    pub fetch: bool,
+    /// Accept the first suggestion without prompting
+    pub yes: bool,
+    /// Print the AI prompt instead of creating anything
+    pub show_prompt: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CleanArgs {
+    /// Worktrees without a commit for this many days count as stale
+    pub days: Option<u64>,
+    /// Only report what would be removed
+    pub dry_run: bool,
+    /// Remove the preselected candidates without showing the checklist
+    pub yes: bool,
+    /// Delete the removed worktrees' branches without asking
+    pub delete_branches: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigAction {
+    Get {
+        key: String,
+        show_origin: bool,
+    },
+    Set {
+        key: String,
+        value: String,
+        scope: Scope,
+    },
+    Unset {
+        key: String,
+        scope: Scope,
+    },
+    List {
+        show_origin: bool,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Porcelain,
+    /// `--format` template with `{field}` placeholders
+    Template(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    UnknownOption(String),
+    MissingValue(String),
+    /// Option name and the value it could not take
+    InvalidValue(String, String),
+    UnexpectedArgument(String),
+    UnknownSetting(String),
+}
+
+impl ParseError {
+    pub fn describe(&self, m: &Messages) -> String {
+        match self {
+            ParseError::UnknownOption(o) => m.unknown_option.replacen("%s", o, 1),
+            ParseError::MissingValue(o) => m.missing_value.replacen("%s", o, 1),
+            ParseError::InvalidValue(o, v) => {
+                m.invalid_value.replacen("%s", o, 1).replacen("%s", v, 1)
+            }
+            ParseError::UnexpectedArgument(a) => m.unexpected_argument.replacen("%s", a, 1),
+            ParseError::UnknownSetting(k) => m.unknown_setting.replacen("%s", k, 1),
+        }
+    }
+}
+
+enum Arg {
+    /// `--name` or `-n`, with the inline value of `--name=value` if present
+    Flag(String, Option<String>),
+    Positional(String),
+}
+
+/// Splits raw arguments into flags and positionals. Everything after `--`
+/// is positional.
+struct Args<'a> {
+    iter: std::slice::Iter<'a, String>,
+    only_positional: bool,
+}
+
+impl<'a> Args<'a> {
+    fn new(args: &'a [String]) -> Self {
+        Self {
+            iter: args.iter(),
+            only_positional: false,
+        }
+    }
+
+    fn next_arg(&mut self) -> Option<Arg> {
+        let arg = self.iter.next()?;
+        if self.only_positional || arg == "-" || !arg.starts_with('-') {
+            return Some(Arg::Positional(arg.clone()));
+        }
+        if arg == "--" {
+            self.only_positional = true;
+            return self.next_arg();
+        }
+        match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                Some(Arg::Flag(name.to_string(), Some(value.to_string())))
+            }
+            _ => Some(Arg::Flag(arg.clone(), None)),
+        }
+    }
+
+    /// Value for `flag`, taken inline or from the next argument
+    fn value(&mut self, flag: &str, inline: Option<String>) -> Result<String, ParseError> {
+        if let Some(v) = inline {
+            return Ok(v);
+        }
+        match self.iter.next() {
+            Some(v) if !v.starts_with('-') || v == "-" => Ok(v.clone()),
+            _ => Err(ParseError::MissingValue(flag.to_string())),
+        }
+    }
+
+    /// Errors on the first remaining argument, for commands that take none
+    fn finish(mut self) -> Result<(), ParseError> {
+        match self.next_arg() {
+            None => Ok(()),
+            Some(Arg::Flag(f, _)) => Err(ParseError::UnknownOption(f)),
+            Some(Arg::Positional(p)) => Err(ParseError::UnexpectedArgument(p)),
+        }
+    }
+}
+
+fn no_inline(flag: String, inline: Option<String>) -> Result<(), ParseError> {
+    match inline {
+        Some(_) => Err(ParseError::UnknownOption(flag)),
+        None => Ok(()),
+    }
+}
+
+fn parse_create(mut args: Args) -> Result<CreateArgs, ParseError> {
+    let mut create = CreateArgs::default();
+    let mut words = Vec::new();
+
+    while let Some(arg) = args.next_arg() {
+        match arg {
+            Arg::Positional(w) => words.push(w),
+            Arg::Flag(f, inline) => match f.as_str() {
+                "--branch" | "-b" => create.branch = Some(args.value(&f, inline)?),
+                "--base" => create.base = Some(args.value(&f, inline)?),
+                "--no-ai" => {
+                    no_inline(f, inline)?;
+                    create.no_ai = true;
+                }
+                "--fetch" => {
+                    no_inline(f, inline)?;
+                    create.fetch = true;
+                }
+                "--yes" | "-y" => {
+                    no_inline(f, inline)?;
+                    create.yes = true;
+                }
+                "--show-prompt" => {
+                    no_inline(f, inline)?;
+                    create.show_prompt = true;
+                }
+                _ => return Err(ParseError::UnknownOption(f)),
+            },
+        }
+    }
+
+    create.description = words.join(" ");
+    Ok(create)
+}
+
+fn parse_checkout(mut args: Args) -> Result<Command, ParseError> {
+    let mut target = None;
+    let mut remote = None;
+
+    while let Some(arg) = args.next_arg() {
+        match arg {
+            Arg::Positional(p) if target.is_none() => target = Some(p),
+            Arg::Positional(p) => return Err(ParseError::UnexpectedArgument(p)),
+            Arg::Flag(f, inline) => match f.as_str() {
+                "--remote" => remote = Some(args.value(&f, inline)?),
+                _ => return Err(ParseError::UnknownOption(f)),
+            },
+        }
+    }
+
+    Ok(Command::Checkout { target, remote })
+}
+
+fn parse_list(mut args: Args) -> Result<Command, ParseError> {
+    let mut format = ListFormat::Table;
+
+    while let Some(arg) = args.next_arg() {
+        match arg {
+            Arg::Positional(p) => return Err(ParseError::UnexpectedArgument(p)),
+            Arg::Flag(f, inline) => match f.as_str() {
+                "--json" => {
+                    no_inline(f, inline)?;
+                    format = ListFormat::Json;
+                }
+                "--porcelain" => {
+                    no_inline(f, inline)?;
+                    format = ListFormat::Porcelain;
+                }
+                "--format" => format = ListFormat::Template(args.value(&f, inline)?),
+                _ => return Err(ParseError::UnknownOption(f)),
+            },
+        }
+    }
+
+    Ok(Command::List(format))
+}
+
+fn parse_clean(mut args: Args) -> Result<Command, ParseError> {
+    let mut clean = CleanArgs::default();
+
+    while let Some(arg) = args.next_arg() {
+        match arg {
+            Arg::Positional(p) => return Err(ParseError::UnexpectedArgument(p)),
+            Arg::Flag(f, inline) => match f.as_str() {
+                "--days" => {
+                    let value = args.value(&f, inline)?;
+                    match value.parse() {
+                        Ok(days) => clean.days = Some(days),
+                        Err(_) => return Err(ParseError::InvalidValue(f, value)),
+                    }
+                }
+                "--dry-run" | "-n" => {
+                    no_inline(f, inline)?;
+                    clean.dry_run = true;
+                }
+                "--yes" | "-y" => {
+                    no_inline(f, inline)?;
+                    clean.yes = true;
+                }
+                "--delete-branches" => {
+                    no_inline(f, inline)?;
+                    clean.delete_branches = true;
+                }
+                _ => return Err(ParseError::UnknownOption(f)),
+            },
+        }
+    }
+
+    Ok(Command::Clean(clean))
+}
+
+fn parse_config(mut args: Args) -> Result<Command, ParseError> {
+    let mut words = Vec::new();
+    let mut show_origin = false;
+    let mut scope = Scope::User;
+
+    while let Some(arg) = args.next_arg() {
+        match arg {
+            Arg::Positional(w) => words.push(w),
+            Arg::Flag(f, inline) => {
+                no_inline(f.clone(), inline)?;
+                match f.as_str() {
+                    "--show-origin" => show_origin = true,
+                    "--user" => scope = Scope::User,
+                    "--repo" => scope = Scope::Repo,
+                    "--local" => scope = Scope::GitConfig,
+                    _ => return Err(ParseError::UnknownOption(f)),
+                }
+            }
+        }
+    }
+
+    let mut words = words.into_iter();
+    let action = words.next();
+    let mut key = || {
+        let key = words
+            .next()
+            .ok_or_else(|| ParseError::MissingValue("key".into()))?;
+        if config::is_known_key(&key) {
+            Ok(key)
+        } else {
+            Err(ParseError::UnknownSetting(key))
+        }
+    };
+
+    let action = match action.as_deref() {
+        None | Some("list") | Some("ls") => ConfigAction::List { show_origin },
+        Some("get") => ConfigAction::Get {
+            key: key()?,
+            show_origin,
+        },
+        Some("set") => {
+            let key = key()?;
+            let value = words
+                .next()
+                .ok_or_else(|| ParseError::MissingValue(key.clone()))?;
+            ConfigAction::Set { key, value, scope }
+        }
+        Some("unset") => ConfigAction::Unset { key: key()?, scope },
+        Some(other) => return Err(ParseError::UnexpectedArgument(other.to_string())),
+    };
+
+    if let Some(extra) = words.next() {
+        return Err(ParseError::UnexpectedArgument(extra));
+    }
+    Ok(Command::Config(action))
+}
+
+/// `-c key=value` pairs, in command-line order
+pub type Overrides = Vec<(String, String)>;
+
+/// Takes leading `-c key=value` pairs off the arguments, returning them and
+/// the rest. They override every other configuration layer.
+pub fn split_overrides(args: &[String]) -> Result<(Overrides, &[String]), ParseError> {
+    let mut overrides = Vec::new();
+    let mut rest = args;
+
+    while let Some(first) = rest.first() {
+        if first != "-c" {
+            break;
+        }
+        let pair = rest
+            .get(1)
+            .ok_or_else(|| ParseError::MissingValue("-c".to_string()))?;
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| ParseError::InvalidValue("-c".to_string(), pair.clone()))?;
+        if !config::is_known_key(key) {
+            return Err(ParseError::UnknownSetting(key.to_string()));
+        }
+        overrides.push((key.to_string(), value.to_string()));
+        rest = &rest[2..];
+    }
+
+    Ok((overrides, rest))
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Command, ParseError> {
+    let first = match args.first() {
+        Some(a) => a.as_str(),
+        None => return Ok(Command::Interactive),
+    };
+    let rest = Args::new(&args[1..]);
+
+    let command = match first {
+        "create" | "c" => Command::Create(parse_create(rest)?),
+        "checkout" | "co" => parse_checkout(rest)?,
+        "list" | "l" | "ls" => parse_list(rest)?,
+        "remove" | "rm" => {
+            rest.finish()?;
+            Command::Remove
+        }
+        "clean" | "prune" => parse_clean(rest)?,
+        "switch" | "s" => {
+            let mut rest = rest;
+            let query = match rest.next_arg() {
+                None => None,
+                Some(Arg::Positional(q)) => Some(q),
+                Some(Arg::Flag(f, _)) => return Err(ParseError::UnknownOption(f)),
+            };
+            rest.finish()?;
+            Command::Switch { query }
+        }
+        "settings" => {
+            rest.finish()?;
+            Command::Settings
+        }
+        "config" => parse_config(rest)?,
+        "init" => {
+            let mut rest = rest;
+            let shell = match rest.next_arg() {
+                None => None,
+                Some(Arg::Positional(s)) => Some(s),
+                Some(Arg::Flag(f, _)) => return Err(ParseError::UnknownOption(f)),
+            };
+            rest.finish()?;
+            Command::Init { shell }
+        }
+        "help" | "-h" | "--help" => Command::Help,
+        // `shuu "<description>"` and `shuu --branch x "<description>"`
+        _ => Command::Create(parse_create(Args::new(args))?),
+    };
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &[&str]) -> Result<Command, ParseError> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse(&args)
+    }
+
+    fn create(args: &[&str]) -> CreateArgs {
+        match parse_str(args).unwrap() {
+            Command::Create(c) => c,
+            other => panic!("expected create, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_no_args_is_interactive() {
+        assert_eq!(parse_str(&[]).unwrap(), Command::Interactive);
+    }
+
+    #[test]
+    fn test_parse_aliases() {
+        assert_eq!(
+            parse_str(&["ls"]).unwrap(),
+            Command::List(ListFormat::Table)
+        );
+        assert_eq!(parse_str(&["l"]).unwrap(), Command::List(ListFormat::Table));
+        assert_eq!(parse_str(&["rm"]).unwrap(), Command::Remove);
+        assert_eq!(parse_str(&["s"]).unwrap(), Command::Switch { query: None });
+        assert_eq!(parse_str(&["-h"]).unwrap(), Command::Help);
+        assert!(matches!(parse_str(&["c"]).unwrap(), Command::Create(_)));
+    }
+
+    #[test]
+    fn test_parse_create_description_words() {
+        let c = create(&["create", "implement", "auth"]);
+        assert_eq!(c.description, "implement auth");
+        assert!(c.branch.is_none());
+    }
+
+    #[test]
+    fn test_parse_direct_description() {
+        let c = create(&["implement auth"]);
+        assert_eq!(c.description, "implement auth");
+    }
+
+    #[test]
+    fn test_parse_create_flags() {
+        let c = create(&[
+            "create",
+            "--branch",
+            "feat/x",
+            "--base",
+            "origin/main",
+            "--fetch",
+            "--no-ai",
+            "--yes",
+            "desc",
+        ]);
+        assert_eq!(c.branch.as_deref(), Some("feat/x"));
+        assert_eq!(c.base.as_deref(), Some("origin/main"));
+        assert!(c.fetch);
+        assert!(c.no_ai);
+        assert!(c.yes);
+        assert_eq!(c.description, "desc");
+    }
+
+    #[test]
+    fn test_parse_create_inline_values_and_short_flags() {
+        let c = create(&["create", "--branch=fix/y", "--base=v1.2", "-y"]);
+        assert_eq!(c.branch.as_deref(), Some("fix/y"));
+        assert_eq!(c.base.as_deref(), Some("v1.2"));
+        assert!(c.yes);
+        assert!(!c.show_prompt);
+        assert!(create(&["create", "--show-prompt", "auth"]).show_prompt);
+
+        let c = create(&["c", "-b", "fix/z"]);
+        assert_eq!(c.branch.as_deref(), Some("fix/z"));
+    }
+
+    #[test]
+    fn test_parse_direct_description_with_flags() {
+        let c = create(&["--yes", "implement auth"]);
+        assert!(c.yes);
+        assert_eq!(c.description, "implement auth");
+    }
+
+    #[test]
+    fn test_parse_double_dash_ends_options() {
+        let c = create(&["create", "--", "--yes", "is", "text"]);
+        assert!(!c.yes);
+        assert_eq!(c.description, "--yes is text");
+    }
+
+    #[test]
+    fn test_parse_missing_value() {
+        assert_eq!(
+            parse_str(&["create", "--branch"]),
+            Err(ParseError::MissingValue("--branch".to_string()))
+        );
+        assert_eq!(
+            parse_str(&["create", "--base", "--yes"]),
+            Err(ParseError::MissingValue("--base".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_option() {
+        assert_eq!(
+            parse_str(&["create", "--frobnicate"]),
+            Err(ParseError::UnknownOption("--frobnicate".to_string()))
+        );
+        assert_eq!(
+            parse_str(&["create", "--yes=1"]),
+            Err(ParseError::UnknownOption("--yes".to_string()))
+        );
+        assert_eq!(
+            parse_str(&["list", "--csv"]),
+            Err(ParseError::UnknownOption("--csv".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_unexpected_argument() {
+        assert_eq!(
+            parse_str(&["list", "extra"]),
+            Err(ParseError::UnexpectedArgument("extra".to_string()))
+        );
+        assert_eq!(
+            parse_str(&["init", "zsh", "bash"]),
+            Err(ParseError::UnexpectedArgument("bash".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_list_formats() {
+        assert_eq!(
+            parse_str(&["list", "--json"]).unwrap(),
+            Command::List(ListFormat::Json)
+        );
+        assert_eq!(
+            parse_str(&["list", "--porcelain"]).unwrap(),
+            Command::List(ListFormat::Porcelain)
+        );
+        assert_eq!(
+            parse_str(&["ls", "--format", "{path}\\t{branch}"]).unwrap(),
+            Command::List(ListFormat::Template("{path}\\t{branch}".to_string()))
+        );
+        assert_eq!(
+            parse_str(&["ls", "--format={path}"]).unwrap(),
+            Command::List(ListFormat::Template("{path}".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_checkout() {
+        assert_eq!(
+            parse_str(&["co"]).unwrap(),
+            Command::Checkout {
+                target: None,
+                remote: None
+            }
+        );
+        assert_eq!(
+            parse_str(&["checkout", "#12", "--remote", "upstream"]).unwrap(),
+            Command::Checkout {
+                target: Some("#12".to_string()),
+                remote: Some("upstream".to_string())
+            }
+        );
+        assert_eq!(
+            parse_str(&["checkout", "a", "b"]),
+            Err(ParseError::UnexpectedArgument("b".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_init() {
+        assert_eq!(
+            parse_str(&["init", "fish"]).unwrap(),
+            Command::Init {
+                shell: Some("fish".to_string())
+            }
+        );
+        assert_eq!(parse_str(&["init"]).unwrap(), Command::Init { shell: None });
+    }
+
+    #[test]
+    fn test_parse_clean() {
+        assert_eq!(
+            parse_str(&["prune"]).unwrap(),
+            Command::Clean(CleanArgs::default())
+        );
+        assert_eq!(
+            parse_str(&["clean", "--days=14", "--dry-run", "-y", "--delete-branches"]).unwrap(),
+            Command::Clean(CleanArgs {
+                days: Some(14),
+                dry_run: true,
+                yes: true,
+                delete_branches: true,
+            })
+        );
+        assert_eq!(
+            parse_str(&["clean", "--days", "soon"]),
+            Err(ParseError::InvalidValue(
+                "--days".to_string(),
+                "soon".to_string()
+            ))
+        );
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(
+            parse_str(&["config"]).unwrap(),
+            Command::Config(ConfigAction::List { show_origin: false })
+        );
+        assert_eq!(
+            parse_str(&["config", "get", "base", "--show-origin"]).unwrap(),
+            Command::Config(ConfigAction::Get {
+                key: "base".to_string(),
+                show_origin: true,
+            })
+        );
+        assert_eq!(
+            parse_str(&["config", "set", "--repo", "ai.instructions", "use jira ids"]).unwrap(),
+            Command::Config(ConfigAction::Set {
+                key: "ai.instructions".to_string(),
+                value: "use jira ids".to_string(),
+                scope: Scope::Repo,
+            })
+        );
+        assert_eq!(
+            parse_str(&["config", "unset", "fetch", "--local"]).unwrap(),
+            Command::Config(ConfigAction::Unset {
+                key: "fetch".to_string(),
+                scope: Scope::GitConfig,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert_eq!(
+            parse_str(&["config", "get", "colour"]),
+            Err(ParseError::UnknownSetting("colour".to_string()))
+        );
+        assert_eq!(
+            parse_str(&["config", "set", "base"]),
+            Err(ParseError::MissingValue("base".to_string()))
+        );
+        assert_eq!(
+            parse_str(&["config", "frob"]),
+            Err(ParseError::UnexpectedArgument("frob".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_split_overrides() {
+        let args = strings(&["-c", "fetch=true", "-c", "base=origin/dev", "create", "-c"]);
+        let (overrides, rest) = split_overrides(&args).unwrap();
+        assert_eq!(
+            overrides,
+            vec![
+                ("fetch".to_string(), "true".to_string()),
+                ("base".to_string(), "origin/dev".to_string()),
+            ]
+        );
+        assert_eq!(rest, &args[4..]);
+
+        let args = strings(&["-c", "fetch"]);
+        assert_eq!(
+            split_overrides(&args),
+            Err(ParseError::InvalidValue(
+                "-c".to_string(),
+                "fetch".to_string()
+            ))
+        );
+        let args = strings(&["-c", "nope=1"]);
+        assert_eq!(
+            split_overrides(&args),
+            Err(ParseError::UnknownSetting("nope".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_switch_query() {
+        assert_eq!(
+            parse_str(&["switch", "auth"]).unwrap(),
+            Command::Switch {
+                query: Some("auth".to_string())
+            }
+        );
+        assert_eq!(
+            parse_str(&["s", "-"]).unwrap(),
+            Command::Switch {
+                query: Some("-".to_string())
+            }
+        );
+        assert_eq!(
+            parse_str(&["s", "a", "b"]),
+            Err(ParseError::UnexpectedArgument("b".to_string()))
+        );
+    }
+}
@@ -1,9 +1,19 @@
+use crate::cli::{CleanArgs, ConfigAction, CreateArgs, ListFormat};
+use crate::config::Scope;
+use crate::hooks::{self, Hook};
 use crate::i18n;
-use crate::menu::select_menu;
-use crate::{ai, banner, config, git};
+use crate::menu::{
+    multi_select_menu, multi_select_menu_with_preview, select_menu, select_menu_with_preview,
+    Preview,
+};
+use crate::{ai, banner, config, fuzzy, git, naming, output, seed, shell, state};
 use crate::{err, info, success, App};
 use crate::{BLUE, BOLD, CYAN, DIM, GREEN, NC, RED, VERSION, YELLOW};
-use std::io::{self, Write};
+use crate::{EXIT_CANCELLED, EXIT_FAILURE, EXIT_USAGE};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 fn read_line_prompt(prompt: &str) -> String {
     eprint!("{BOLD}{prompt}{NC} ");
@@ -22,118 +32,736 @@ fn confirm(prompt: &str) -> bool {
     answer == "y" || answer == "Y"
 }
 
-fn require_git_repo(app: &App) {
-    if !git::is_git_repo() {
-        err(app.messages.not_git_repo);
-        std::process::exit(1);
+/// Hands `path` to the shell wrapper. Returns false if there is none, in
+/// which case the shell stays put and `shuu switch -` is left alone.
+fn hand_off_dir(app: &App, path: &str) -> bool {
+    match shell::hand_off(path) {
+        Ok(true) => {
+            remember_current_worktree(app, path);
+            true
+        }
+        Ok(false) => false,
+        Err(e) => {
+            err(&format!("{}: {}", app.messages.handoff_failed, e));
+            false
+        }
     }
 }
 
-pub fn cmd_create(app: &App, description: String) {
-    require_git_repo(app);
+/// Records the worktree being left for `shuu switch -`
+fn remember_current_worktree(app: &App, target: &str) {
+    let worktrees = git::worktree_list();
+    let current = match git::current_worktree(&worktrees) {
+        Some(i) => &worktrees[i],
+        None => return,
+    };
+    if current.path == target {
+        return;
+    }
+    if let Err(e) = state::set_previous_worktree(&current.path) {
+        err(&format!("{}: {}", app.messages.state_write_failed, e));
+    }
+}
+
+/// Login name for the `{user}` path placeholder
+fn user_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_else(|_| "user".to_string())
+}
+
+/// Directory for a new worktree on `branch`, from the `worktree.path`
+/// template. Exits if it already exists or would overlap another worktree.
+fn new_worktree_path(app: &App, branch: &str) -> String {
     let m = app.messages;
+    let worktrees = git::worktree_list();
+    let template = config::worktree_path_template(&app.config);
 
-    let worktrees_dir = match git::worktrees_dir() {
-        Some(d) => d,
+    let now = unix_now();
+    let date = output::iso_date(now + output::utc_offset(now));
+    let user = user_name();
+    let vars = git::PathVars {
+        branch,
+        user: &user,
+        date: &date,
+    };
+    let home = dirs::home_dir().unwrap_or_default();
+    let wt_path = match worktrees
+        .first()
+        .and_then(|main| git::compute_worktree_path(&template, &main.path, &vars, &home))
+    {
+        Some(p) => p,
         None => {
-            err("Could not determine worktrees directory");
-            std::process::exit(1);
+            err(&m.wt_path_invalid.replacen("%s", &template, 1));
+            std::process::exit(EXIT_FAILURE);
         }
     };
 
-    let description = if description.is_empty() {
-        let desc = read_line_prompt(m.what_to_implement);
-        if desc.is_empty() {
-            err(m.enter_desc);
-            std::process::exit(1);
+    if Path::new(&wt_path).exists() {
+        err(&format!("{}: {}", m.wt_already_exists, wt_path));
+        std::process::exit(EXIT_FAILURE);
+    }
+    if let Some(other) = git::overlapping_worktree(&wt_path, &worktrees) {
+        err(&m
+            .wt_path_overlaps
+            .replacen("%s", &wt_path, 1)
+            .replacen("%s", &other.path, 1));
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    if let Some(parent) = Path::new(&wt_path).parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    wt_path
+}
+
+/// Runs the post-create hook in a new worktree. If it fails, removes the
+/// worktree again (and the branch, if it was created) when `hooks.on_failure`
+/// says so or the user agrees, and exits.
+fn after_create(app: &App, wt_path: &str, branch: &str, base: Option<&str>, created_branch: bool) {
+    let m = app.messages;
+    let ctx = hooks::Context {
+        path: wt_path,
+        branch: Some(branch),
+        base,
+    };
+    if hooks::run(app, Hook::PostCreate, &ctx) != hooks::Outcome::Failed {
+        return;
+    }
+
+    let policy = app
+        .config
+        .get("hooks.on_failure")
+        .unwrap_or("ask")
+        .to_string();
+    let rollback = match policy.as_str() {
+        "rollback" => true,
+        "ask" if io::stdin().is_terminal() => {
+            confirm(&format!("{} [y/N]:", m.hook_rollback_prompt))
         }
-        desc
+        _ => false,
+    };
+    if !rollback {
+        info(&m.hook_kept.replacen("%s", wt_path, 1));
+        return;
+    }
+
+    if !git::worktree_force_remove(wt_path) {
+        err(&format!("{}: {}", m.wt_remove_failed, wt_path));
+    } else if created_branch && !git::branch_force_delete(branch) {
+        err(&format!("{}: {}", m.branch_delete_failed, branch));
+    } else {
+        info(&m.hook_rolled_back.replacen("%s", wt_path, 1));
+    }
+    std::process::exit(EXIT_FAILURE);
+}
+
+/// Brings the untracked files matched by `files.*` over from the main
+/// worktree. Clones get a size estimate and a progress line first. Returns
+/// the line for the creation summary.
+fn seed_local_files(app: &App, wt_path: &str) -> Option<String> {
+    let m = app.messages;
+    let config = &app.config;
+    let rules = seed::rules(config);
+    if rules.is_empty() {
+        return None;
+    }
+    let fallback = match config.get("files.clone_fallback") {
+        Some("hardlink") => seed::Method::Hardlink,
+        _ => seed::Method::Copy,
+    };
+    let main = git::main_worktree()?;
+    let main_dir = Path::new(&main);
+    let items = seed::find(main_dir, &git::untracked_paths(&main), &rules);
+
+    let clones: Vec<&str> = items
+        .iter()
+        .filter(|(_, method)| *method == seed::Method::Clone)
+        .map(|(rel, _)| rel.as_str())
+        .collect();
+    // Walking the tree to size it is only worth it when progress is shown
+    let mut total = 0;
+    if !clones.is_empty() {
+        total = seed::estimate(main_dir, &items);
+        info(
+            &m.seed_cloning
+                .replacen("%s", &clones.join(", "), 1)
+                .replacen("%s", &output::human_size(total), 1),
+        );
+    }
+
+    let show_progress = !clones.is_empty() && io::stderr().is_terminal();
+    let mut last_drawn = Instant::now();
+    let mut progress = |done: u64| {
+        if !show_progress || last_drawn.elapsed() < Duration::from_millis(100) {
+            return;
+        }
+        last_drawn = Instant::now();
+        let percent = (done * 100).checked_div(total).unwrap_or(100).min(100);
+        eprint!(
+            "\r\x1b[K  {DIM}{} / {} ({}%){NC}",
+            output::human_size(done),
+            output::human_size(total),
+            percent
+        );
+    };
+    let report = seed::apply(
+        main_dir,
+        Path::new(wt_path),
+        &items,
+        fallback,
+        &mut progress,
+    );
+    if show_progress {
+        eprint!("\r\x1b[K");
+    }
+
+    for (path, reason) in &report.failed {
+        err(&m
+            .seed_failed
+            .replacen("%s", path, 1)
+            .replacen("%s", reason, 1));
+    }
+    let counts = [
+        (report.cloned.len(), m.seed_cloned),
+        (report.copied.len(), m.seed_copied),
+        (report.hardlinked.len(), m.seed_hardlinked),
+        (report.symlinked.len(), m.seed_symlinked),
+        (report.skipped.len(), m.seed_skipped),
+    ];
+    let summary: Vec<String> = counts
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, text)| text.replacen("%s", &n.to_string(), 1))
+        .collect();
+    Some(summary.join(", ")).filter(|s| !s.is_empty())
+}
+
+/// Prints the new worktree path on stdout and hands it to the shell wrapper
+fn land_in_worktree(app: &App, wt_path: &str) {
+    let handed = hand_off_dir(app, wt_path);
+    if !shell::hands_off_on_stdout() {
+        println!("{}", wt_path);
+    }
+    if !handed {
+        eprintln!(
+            "{CYAN}\u{25b8}{NC} {} {BOLD}shuu switch{NC}",
+            app.messages.to_switch
+        );
+    }
+}
+
+fn require_git_repo(app: &App) {
+    if !git::is_git_repo() {
+        err(app.messages.not_git_repo);
+        std::process::exit(1);
+    }
+}
+
+/// Suggestions corrected into usable new branch names, without duplicates.
+/// If none can be used, the reason is shown and the name derived from the
+/// description is tried; the list may still end up empty.
+fn branch_suggestions(
+    app: &App,
+    description: &str,
+    feedback: &str,
+    count: usize,
+    no_ai: bool,
+) -> Vec<String> {
+    let m = app.messages;
+    let rules = naming::Rules::load(&app.config);
+    let existing = naming::Existing::load();
+    let raw = if no_ai {
+        vec![ai::fallback_branch_name(description)]
     } else {
-        description
+        ai::generate_branch_names(&app.config, description, feedback, count, m)
     };
 
-    let mut suggested_name = ai::generate_branch_name(&description, "", m);
+    let mut names: Vec<String> = Vec::new();
+    let mut problem = None;
+    for suggestion in &raw {
+        match naming::correct(suggestion, &rules, &existing) {
+            Ok(name) if !names.contains(&name) => names.push(name),
+            Ok(_) => {}
+            Err(p) => {
+                problem.get_or_insert((suggestion, p));
+            }
+        }
+    }
+    if names.is_empty() {
+        if let Some((suggestion, p)) = problem {
+            err(&p.describe(suggestion, m));
+        }
+        if let Ok(name) = naming::correct(&ai::fallback_branch_name(description), &rules, &existing)
+        {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Interactive branch picker: the suggestion, manual entry, or regeneration
+/// with feedback. Exits with EXIT_CANCELLED if the menu is dismissed.
+fn choose_branch_name(
+    app: &App,
+    description: &str,
+    suggestions: Vec<String>,
+    no_ai: bool,
+) -> String {
+    let m = app.messages;
+    let mut suggestions = suggestions;
 
-    let branch_name = loop {
-        let items = vec![
-            suggested_name.clone(),
-            m.enter_manually.to_string(),
-            m.regenerate.to_string(),
-        ];
+    loop {
+        let mut items = suggestions.clone();
+        items.push(m.enter_manually.to_string());
+        if !no_ai {
+            items.push(m.regenerate.to_string());
+        }
 
         let choice = match select_menu(m.select_branch, &items, m.menu_hint) {
             Some(c) => c,
-            None => std::process::exit(1),
+            None => std::process::exit(EXIT_CANCELLED),
         };
 
-        match choice {
-            0 => break suggested_name.clone(),
-            1 => {
-                let name = read_line_prompt(m.branch_name);
-                if !name.is_empty() {
-                    break name;
-                }
-                err(m.enter_branch_name);
+        if choice < suggestions.len() {
+            return suggestions.swap_remove(choice);
+        }
+        if choice == suggestions.len() {
+            let name = read_line_prompt(m.branch_name);
+            let rules = naming::Rules::load(&app.config);
+            match naming::check_chosen(&name, &rules, &naming::Existing::load()) {
+                Ok(()) => return name,
+                Err(p) => err(&p.describe(&name, m)),
             }
-            2 => {
-                let fb = read_line_prompt(m.feedback);
-                if !fb.is_empty() {
-                    suggested_name = ai::generate_branch_name(&description, &fb, m);
+        } else {
+            let fb = read_line_prompt(m.feedback);
+            if !fb.is_empty() {
+                suggestions = branch_suggestions(
+                    app,
+                    description,
+                    &fb,
+                    config::ai_candidates(&app.config),
+                    false,
+                );
+            } else {
+                err(m.enter_feedback);
+            }
+        }
+    }
+}
+
+/// Picker over local branches, remote branches and tags. The configured
+/// default (or HEAD) comes first. Returns None for HEAD.
+fn choose_base(app: &App) -> Option<String> {
+    let m = app.messages;
+    let default = config::default_base(&app.config);
+    let head = git::current_branch().unwrap_or_else(|| "HEAD".to_string());
+
+    let mut bases: Vec<Option<String>> = Vec::new();
+    let mut labels = Vec::new();
+    if let Some(d) = &default {
+        bases.push(Some(d.clone()));
+        labels.push(format!("{} \u{2605}", d));
+    }
+    bases.push(None);
+    labels.push(m.base_current_head.replacen("%s", &head, 1));
+    for r in git::base_refs() {
+        if default.as_ref() != Some(&r) {
+            labels.push(r.clone());
+            bases.push(Some(r));
+        }
+    }
+
+    match select_menu(m.select_base, &labels, m.menu_hint) {
+        Some(i) => bases.swap_remove(i),
+        None => std::process::exit(EXIT_CANCELLED),
+    }
+}
+
+pub fn cmd_create(app: &App, args: CreateArgs) {
+    require_git_repo(app);
+    let m = app.messages;
+    let interactive = io::stdin().is_terminal();
+
+    let branch_name = match args.branch {
+        Some(branch) => {
+            let rules = naming::Rules::load(&app.config);
+            if let Err(p) = naming::check_chosen(&branch, &rules, &naming::Existing::load()) {
+                err(&p.describe(&branch, m));
+                std::process::exit(EXIT_USAGE);
+            }
+            branch
+        }
+        None => {
+            let description = if args.description.is_empty() {
+                let desc = if interactive {
+                    read_line_prompt(m.what_to_implement)
                 } else {
-                    err(m.enter_feedback);
+                    String::new()
+                };
+                if desc.is_empty() {
+                    err(m.enter_desc);
+                    std::process::exit(EXIT_USAGE);
                 }
+                desc
+            } else {
+                args.description
+            };
+
+            // Without the menu only the first suggestion would be used
+            let count = if args.yes {
+                1
+            } else {
+                config::ai_candidates(&app.config)
+            };
+            if args.show_prompt {
+                println!(
+                    "{}",
+                    ai::branch_prompt(&app.config, &description, "", count, m)
+                );
+                return;
+            }
+
+            if !args.yes && !interactive {
+                err(m.not_a_terminal);
+                std::process::exit(EXIT_USAGE);
+            }
+
+            let mut suggestions = branch_suggestions(app, &description, "", count, args.no_ai);
+
+            if args.yes {
+                if suggestions.is_empty() {
+                    err(m.no_usable_suggestion);
+                    std::process::exit(EXIT_FAILURE);
+                }
+                suggestions.swap_remove(0)
+            } else {
+                choose_branch_name(app, &description, suggestions, args.no_ai)
             }
-            _ => {}
         }
     };
 
-    // Derive worktree directory name from branch (replace / with -)
-    let wt_dirname = branch_name.replace('/', "-");
-    let wt_path = format!("{}/{}", worktrees_dir, wt_dirname);
+    // The base only matters when a new branch is created
+    let base = if args.base.is_some() {
+        args.base
+    } else if interactive && !args.yes && !git::branch_exists(&branch_name) {
+        choose_base(app)
+    } else {
+        config::default_base(&app.config)
+    };
 
-    if std::path::Path::new(&wt_path).is_dir() {
-        err(&format!("{}: {}", m.wt_already_exists, wt_path));
-        std::process::exit(1);
+    if let Some(base) = &base {
+        if args.fetch || config::fetch_base(&app.config) {
+            if let Some((remote, branch)) = git::split_remote_ref(base) {
+                info(&m.fetching.replacen("%s", base, 1));
+                if !git::fetch(&remote, &branch) {
+                    err(&m.fetch_failed.replacen("%s", base, 1));
+                    std::process::exit(EXIT_FAILURE);
+                }
+            }
+        }
     }
 
-    let _ = std::fs::create_dir_all(&worktrees_dir);
+    let wt_path = new_worktree_path(app, &branch_name);
 
     info(m.creating_wt);
-    match git::worktree_add(&wt_path, &branch_name) {
-        Ok(true) => success(m.wt_created),
-        Ok(false) => success(m.wt_created_existing),
+    let created_branch = match git::worktree_add(&wt_path, &branch_name, base.as_deref()) {
+        Ok(true) => {
+            success(m.wt_created);
+            true
+        }
+        Ok(false) => {
+            success(m.wt_created_existing);
+            false
+        }
         Err(()) => {
             err(m.wt_create_failed);
-            std::process::exit(1);
+            std::process::exit(EXIT_FAILURE);
         }
-    }
+    };
 
+    let local_files = seed_local_files(app, &wt_path);
     eprintln!();
     eprintln!("  {DIM}{}{NC} {}", m.path, wt_path);
     eprintln!("  {DIM}{}{NC} {}", m.branch, branch_name);
+    if created_branch {
+        if let Some(base) = &base {
+            eprintln!("  {DIM}{}{NC} {}", m.base, base);
+        }
+    }
+    if let Some(summary) = &local_files {
+        eprintln!("  {DIM}{}{NC} {}", m.local_files, summary);
+    }
     eprintln!();
-    eprintln!("{CYAN}\u{25b8}{NC} {} {BOLD}shuu switch{NC}", m.to_switch);
+
+    let base = base.as_deref().filter(|_| created_branch);
+    after_create(app, &wt_path, &branch_name, base, created_branch);
+    land_in_worktree(app, &wt_path);
 }
 
-pub fn cmd_list(app: &App) {
+pub fn cmd_checkout(app: &App, target: Option<String>, remote: Option<String>) {
+    require_git_repo(app);
+    let m = app.messages;
+
+    if let Some(number) = target.as_deref().and_then(git::pull_request_number) {
+        checkout_pull_request(app, number, remote);
+        return;
+    }
+
+    if let Some(remote) = remote.as_ref().filter(|r| !git::remotes().contains(r)) {
+        err(&m.unknown_remote.replacen("%s", remote, 1));
+        std::process::exit(EXIT_USAGE);
+    }
+
+    info(m.fetching_remotes);
+    if !git::fetch_all() {
+        err(m.fetch_remotes_failed);
+    }
+
+    let branches = match &remote {
+        Some(remote) => git::remote_branches_on(remote),
+        None => git::remote_branches(),
+    };
+    if branches.is_empty() {
+        info(m.no_remote_branches);
+        return;
+    }
+
+    let query = target.unwrap_or_default();
+    let exact = branches.iter().position(|b| {
+        *b == query || git::split_remote_ref(b).is_some_and(|(_, branch)| branch == query)
+    });
+    let matches = match exact {
+        Some(i) => vec![i],
+        None => fuzzy::filter(&query, &branches),
+    };
+
+    let remote_ref = match matches.as_slice() {
+        [] => {
+            err(&m.no_match.replacen("%s", &query, 1));
+            std::process::exit(EXIT_FAILURE);
+        }
+        [only] if !query.is_empty() => branches[*only].clone(),
+        _ => {
+            let labels: Vec<String> = matches.iter().map(|&i| branches[i].clone()).collect();
+            match select_menu(m.select_remote_branch, &labels, m.menu_hint) {
+                Some(s) => labels[s].clone(),
+                None => std::process::exit(EXIT_CANCELLED),
+            }
+        }
+    };
+
+    let branch = match git::split_remote_ref(&remote_ref) {
+        Some((_, branch)) => branch,
+        None => remote_ref.clone(),
+    };
+    let wt_path = new_worktree_path(app, &branch);
+
+    info(m.creating_wt);
+    let created_branch = !git::branch_exists(&branch);
+    let mut tracking = remote_ref.clone();
+    if !created_branch {
+        // Reuse the local branch rather than resetting it to the remote
+        if git::worktree_add(&wt_path, &branch, None).is_err() {
+            err(m.wt_create_failed);
+            std::process::exit(EXIT_FAILURE);
+        }
+        success(m.wt_created_existing);
+        match git::upstream(&branch) {
+            Some(upstream) if upstream != remote_ref => {
+                info(
+                    &m.upstream_differs
+                        .replacen("%s", &branch, 1)
+                        .replacen("%s", &upstream, 1)
+                        .replacen("%s", &remote_ref, 1),
+                );
+                tracking = upstream;
+            }
+            Some(_) => {}
+            None => {
+                if !git::set_upstream(&branch, &remote_ref) {
+                    err(&m.upstream_set_failed.replacen("%s", &branch, 1).replacen(
+                        "%s",
+                        &remote_ref,
+                        1,
+                    ));
+                }
+            }
+        }
+    } else {
+        if !git::worktree_add_tracking(&wt_path, &branch, &remote_ref) {
+            err(m.wt_create_failed);
+            std::process::exit(EXIT_FAILURE);
+        }
+        success(m.wt_created);
+    }
+
+    let local_files = seed_local_files(app, &wt_path);
+    eprintln!();
+    eprintln!("  {DIM}{}{NC} {}", m.path, wt_path);
+    eprintln!("  {DIM}{}{NC} {}", m.branch, branch);
+    eprintln!("  {DIM}{}{NC} {}", m.tracking, tracking);
+    if let Some(summary) = &local_files {
+        eprintln!("  {DIM}{}{NC} {}", m.local_files, summary);
+    }
+    eprintln!();
+
+    let base = Some(remote_ref.as_str()).filter(|_| created_branch);
+    after_create(app, &wt_path, &branch, base, created_branch);
+    land_in_worktree(app, &wt_path);
+}
+
+fn checkout_pull_request(app: &App, number: u32, remote: Option<String>) {
+    let m = app.messages;
+    let number_str = number.to_string();
+
+    let remote = match remote.or_else(git::default_remote) {
+        Some(r) => r,
+        None => {
+            err(m.no_remote);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+
+    let branch = format!("pr/{}", number);
+    let wt_path = new_worktree_path(app, &branch);
+
+    info(&m.fetching_pr.replacen("%s", &number_str, 1));
+    let existed = git::branch_exists(&branch);
+    if !git::fetch_pull_request(&remote, number, &branch) {
+        err(&m.pr_fetch_failed.replacen("%s", &number_str, 1));
+        if existed {
+            info(&m.pr_branch_kept.replacen("%s", &branch, 1));
+        }
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    info(m.creating_wt);
+    if git::worktree_add(&wt_path, &branch, None).is_err() {
+        err(m.wt_create_failed);
+        std::process::exit(EXIT_FAILURE);
+    }
+    success(m.wt_created);
+
+    let local_files = seed_local_files(app, &wt_path);
+    eprintln!();
+    eprintln!("  {DIM}{}{NC} {}", m.path, wt_path);
+    eprintln!("  {DIM}{}{NC} {}", m.branch, branch);
+    eprintln!(
+        "  {DIM}{}{NC} {} refs/pull/{}/head",
+        m.tracking, remote, number
+    );
+    if let Some(summary) = &local_files {
+        eprintln!("  {DIM}{}{NC} {}", m.local_files, summary);
+    }
+    eprintln!();
+
+    let pull_ref = format!("refs/pull/{}/head", number);
+    after_create(app, &wt_path, &branch, Some(&pull_ref), !existed);
+    land_in_worktree(app, &wt_path);
+}
+
+pub fn cmd_list(app: &App, format: ListFormat) {
     require_git_repo(app);
     let m = app.messages;
 
-    let main_wt = git::main_worktree().unwrap_or_default();
     let worktrees = git::worktree_list();
+    let main_wt = worktrees
+        .first()
+        .map(|wt| wt.path.clone())
+        .unwrap_or_default();
+    let main_ref = git::main_branch_ref();
+    let current = git::current_worktree(&worktrees);
+
+    git::load_statuses(&worktrees, main_ref.as_deref());
+
+    let entries: Vec<_> = worktrees
+        .iter()
+        .enumerate()
+        .map(|(i, wt)| output::Entry {
+            wt,
+            is_main: wt.path == main_wt,
+            is_current: current == Some(i),
+            status: wt.status(main_ref.as_deref()),
+        })
+        .collect();
+
+    let template = match format {
+        ListFormat::Table => None,
+        ListFormat::Json => {
+            let json: Vec<_> = entries.iter().map(|e| e.to_json()).collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json).unwrap_or_default()
+            );
+            return;
+        }
+        ListFormat::Porcelain => Some(output::PORCELAIN_FORMAT.to_string()),
+        ListFormat::Template(t) => Some(t),
+    };
+
+    if let Some(template) = template {
+        let lines: Result<Vec<String>, _> = entries.iter().map(|e| e.render(&template)).collect();
+        match lines {
+            Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+            Err(e) => {
+                err(
+                    &m.list_format_invalid.replacen("%s", &template, 1).replacen(
+                        "%s",
+                        &e.describe(m),
+                        1,
+                    ),
+                );
+                std::process::exit(EXIT_USAGE);
+            }
+        }
+        return;
+    }
 
     eprintln!("\n{BOLD}{}{NC}\n", m.list_title);
 
-    for wt in &worktrees {
-        if wt.path == main_wt {
+    let now = unix_now();
+
+    for entry in &entries {
+        let wt = entry.wt;
+        let mut state = String::new();
+        if let Some(reason) = &wt.locked {
+            state.push_str(&format!("  {YELLOW}[{}", m.wt_locked));
+            if !reason.is_empty() {
+                state.push_str(&format!(": {}", reason));
+            }
+            state.push_str(&format!("]{NC}"));
+        }
+        if wt.prunable.is_some() {
+            state.push_str(&format!("  {RED}[{}]{NC}", m.wt_prunable));
+        }
+        let merged = entry.status.is_some_and(|s| s.merged());
+        if merged && !entry.is_main && wt.branch.is_some() {
+            state.push_str(&format!("  {CYAN}[{}]{NC}", m.status_merged));
+        }
+
+        if entry.is_main {
             eprintln!(
-                "  {YELLOW}\u{2605}{NC} {BOLD}{:<50}{NC} {DIM}{}{NC}  {GREEN}{}{NC}",
-                wt.path, wt.hash, wt.branch
+                "  {YELLOW}\u{2605}{NC} {BOLD}{:<50}{NC} {DIM}{:<7}{NC}  {GREEN}{}{NC}{}",
+                wt.path,
+                wt.short_head(),
+                wt.label(),
+                state
             );
         } else {
             eprintln!(
-                "    {:<50} {DIM}{}{NC}  {BLUE}{}{NC}",
-                wt.path, wt.hash, wt.branch
+                "    {:<50} {DIM}{:<7}{NC}  {BLUE}{}{NC}{}",
+                wt.path,
+                wt.short_head(),
+                wt.label(),
+                state
+            );
+        }
+
+        if let Some(status) = entry.status {
+            eprintln!(
+                "      {}",
+                status_summary(app, status, main_ref.as_deref(), now)
             );
         }
     }
@@ -141,6 +769,123 @@ pub fn cmd_list(app: &App) {
     eprintln!();
 }
 
+/// Seconds since the Unix epoch
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// `~modified ?untracked`, leaving out zero counts
+fn change_counts(status: &git::WorktreeStatus) -> String {
+    let mut changes = Vec::new();
+    if status.modified > 0 {
+        changes.push(format!("~{}", status.modified));
+    }
+    if status.untracked > 0 {
+        changes.push(format!("?{}", status.untracked));
+    }
+    changes.join(" ")
+}
+
+/// One-line summary: changes, upstream, main branch and last commit
+fn status_summary(
+    app: &App,
+    status: &git::WorktreeStatus,
+    main_ref: Option<&str>,
+    now: i64,
+) -> String {
+    let m = app.messages;
+    let mut parts = Vec::new();
+
+    if status.is_dirty() {
+        parts.push(format!("{YELLOW}{}{NC}", change_counts(status)));
+    } else {
+        parts.push(format!("{GREEN}{}{NC}", m.status_clean));
+    }
+
+    if status.upstream_gone() {
+        parts.push(format!("{RED}{}{NC}", m.status_upstream_gone));
+    } else if let Some((ahead, behind)) = status.upstream_ahead_behind {
+        parts.push(format!("\u{2191}{} \u{2193}{}", ahead, behind));
+    }
+
+    if let (Some(main), Some((ahead, behind))) = (main_ref, status.main_ahead_behind) {
+        parts.push(format!(
+            "{DIM}{}{NC} \u{2191}{} \u{2193}{}",
+            main, ahead, behind
+        ));
+    }
+
+    if status.last_commit_time > 0 {
+        parts.push(format!(
+            "{DIM}{}  {}{NC}",
+            output::relative_age(now - status.last_commit_time),
+            status.last_commit_subject
+        ));
+    }
+
+    parts.join("   ")
+}
+
+/// Maximum lines shown per section of the removal report
+const REPORT_LIMIT: usize = 5;
+
+fn print_report_section(app: &App, heading: &str, lines: &[String]) {
+    if lines.is_empty() {
+        return;
+    }
+    eprintln!(
+        "\n  {YELLOW}{}{NC}",
+        heading.replacen("%s", &lines.len().to_string(), 1)
+    );
+    for line in lines.iter().take(REPORT_LIMIT) {
+        eprintln!("    {DIM}{}{NC}", line);
+    }
+    if lines.len() > REPORT_LIMIT {
+        let more = (lines.len() - REPORT_LIMIT).to_string();
+        eprintln!(
+            "    {DIM}{}{NC}",
+            app.messages.report_more.replacen("%s", &more, 1)
+        );
+    }
+}
+
+/// Lists what removing the worktree could lose, shown before confirming
+fn print_removal_report(app: &App, report: &git::RemovalReport, main_ref: Option<&str>) {
+    let m = app.messages;
+    if report.is_empty() {
+        eprintln!("\n  {GREEN}\u{2713}{NC} {}", m.report_clean);
+        eprintln!();
+        return;
+    }
+
+    print_report_section(app, m.report_changed, &report.changed_files);
+    print_report_section(app, m.report_unpushed, &report.unpushed);
+    if let Some(main) = main_ref {
+        let heading = m.report_unmerged.replacen("%s", main, 1);
+        print_report_section(app, &heading, &report.unmerged);
+    }
+    print_report_section(app, m.report_stashes, &report.stashes);
+    eprintln!();
+}
+
+/// Asks before a destructive step. When work would be lost, a plain "y" is
+/// not enough: the user has to type `word` back.
+fn confirm_force(app: &App, question: &str, typed: bool, word: &str) -> bool {
+    if !typed {
+        return confirm(&format!("{} [y/N]:", question));
+    }
+    eprintln!("{RED}{BOLD}{}{NC}", question);
+    let prompt = app.messages.type_to_confirm.replacen("%s", word, 1);
+    let matched = read_line_prompt(&prompt) == word;
+    if !matched {
+        info(app.messages.cancelled);
+    }
+    matched
+}
+
 pub fn cmd_remove(app: &App) {
     require_git_repo(app);
     let m = app.messages;
@@ -148,7 +893,10 @@ pub fn cmd_remove(app: &App) {
     let main_wt = git::main_worktree().unwrap_or_default();
     let worktrees = git::worktree_list();
 
-    let removable: Vec<_> = worktrees.iter().filter(|wt| wt.path != main_wt).collect();
+    let removable: Vec<_> = worktrees
+        .iter()
+        .filter(|wt| wt.path != main_wt && !wt.bare)
+        .collect();
 
     if removable.is_empty() {
         info(m.no_wt_to_remove);
@@ -157,97 +905,481 @@ pub fn cmd_remove(app: &App) {
 
     let labels: Vec<String> = removable
         .iter()
-        .map(|wt| format!("{}  {}", wt.branch, wt.path))
+        .map(|wt| format!("{}  {}", wt.label(), wt.path))
         .collect();
 
-    let selected = match select_menu(m.select_wt_remove, &labels, m.menu_hint) {
-        Some(s) => s,
-        None => return,
+    let main_ref = git::main_branch_ref();
+    let mut preview = Preview::new(|i| worktree_preview(app, removable[i], main_ref.as_deref()));
+    let hint = format!("{}  {}", m.multi_menu_hint, m.hint_preview);
+    let list =
+        match multi_select_menu_with_preview(m.select_wt_remove, &labels, &[], &hint, &mut preview)
+        {
+            Some(list) => list,
+            None => std::process::exit(EXIT_CANCELLED),
+        };
+    // Nothing is checked up front, so Enter alone removes the highlighted one
+    let selected = match (list.checked.is_empty(), list.highlighted) {
+        (false, _) => list.checked,
+        (true, Some(i)) => vec![i],
+        (true, None) => return,
     };
 
-    let target = &removable[selected];
+    let targets: Vec<_> = selected
+        .iter()
+        .map(|&i| {
+            let wt = removable[i];
+            (wt, git::removal_report(wt, main_ref.as_deref()))
+        })
+        .collect();
 
     // Confirm
     eprintln!("\n{RED}{BOLD}{}{NC}", m.confirm_delete);
-    eprintln!("  {} {}", m.path, target.path);
-    eprintln!("  {} {}", m.branch, target.branch);
+    for (target, report) in &targets {
+        eprintln!("  {} {}", m.path, target.path);
+        eprintln!("  {} {}", m.branch, target.label());
+        print_removal_report(app, report, main_ref.as_deref());
+    }
 
     if !confirm("[y/N]:") {
         info(m.cancelled);
         return;
     }
 
-    // Remove worktree
-    if git::worktree_remove(&target.path) {
+    let mut failed = false;
+    for (target, report) in &targets {
+        if !remove_worktree(app, target, report) {
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(EXIT_FAILURE);
+    }
+}
+
+/// Runs the pre-remove hook in `wt`. False if it failed, which keeps the
+/// worktree.
+fn before_remove(app: &App, wt: &git::Worktree) -> bool {
+    let ctx = hooks::Context {
+        path: &wt.path,
+        branch: wt.branch_name(),
+        base: None,
+    };
+    if hooks::run(app, Hook::PreRemove, &ctx) == hooks::Outcome::Failed {
+        err(&app.messages.hook_remove_aborted.replacen("%s", &wt.path, 1));
+        return false;
+    }
+    true
+}
+
+/// Removes one confirmed worktree, forcing and deleting its branch only when
+/// the user agrees. Returns false if the worktree was left in place.
+fn remove_worktree(app: &App, target: &git::Worktree, report: &git::RemovalReport) -> bool {
+    let m = app.messages;
+
+    // The word to type before discarding work: the branch, or the directory name
+    let confirm_word = target.branch_name().unwrap_or_else(|| {
+        Path::new(&target.path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(&target.path)
+    });
+
+    // Uncommitted files make a plain remove fail, so go straight to forcing.
+    // Either way the hook runs only once removal is certain.
+    let plain = !report.loses_files();
+    if plain && !before_remove(app, target) {
+        return false;
+    }
+    if plain && git::worktree_remove(&target.path) {
         success(&format!("{}: {}", m.wt_removed, target.path));
     } else {
-        err(m.wt_remove_failed);
-        if confirm(&format!("{} [y/N]:", m.force_remove)) {
-            if git::worktree_force_remove(&target.path) {
-                success(m.wt_force_removed);
-            } else {
-                err(m.force_remove_failed);
-                std::process::exit(1);
-            }
+        if plain {
+            err(&format!("{}: {}", m.wt_remove_failed, target.path));
+        }
+        if !confirm_force(app, m.force_remove, report.loses_files(), confirm_word) {
+            return false;
+        }
+        if !plain && !before_remove(app, target) {
+            return false;
+        }
+        if git::worktree_force_remove(&target.path) {
+            success(&format!("{}: {}", m.wt_force_removed, target.path));
         } else {
-            std::process::exit(1);
+            err(m.force_remove_failed);
+            return false;
         }
     }
 
     // Ask about branch deletion
-    let delete_msg = m.delete_branch.replacen("%s", &target.branch, 1);
+    let branch = match target.branch_name() {
+        Some(b) => b,
+        None => return true,
+    };
+    let delete_msg = m.delete_branch.replacen("%s", branch, 1);
     if confirm(&format!("{} [y/N]:", delete_msg)) {
-        if git::branch_delete(&target.branch) {
-            success(&format!("{}: {}", m.branch_deleted, target.branch));
-        } else if confirm(&format!("{} [y/N]:", m.force_delete_branch)) {
-            git::branch_force_delete(&target.branch);
-            success(&format!("{}: {}", m.branch_force_deleted, target.branch));
+        if git::branch_delete(branch) {
+            success(&format!("{}: {}", m.branch_deleted, branch));
+        } else if confirm_force(app, m.force_delete_branch, report.loses_commits(), branch) {
+            git::branch_force_delete(branch);
+            success(&format!("{}: {}", m.branch_force_deleted, branch));
         }
     }
+    true
+}
+
+/// Worktrees without a commit for this many days count as stale
+const DEFAULT_STALE_DAYS: u64 = 30;
+
+fn staleness_label(app: &App, reason: git::Staleness) -> String {
+    let m = app.messages;
+    match reason {
+        git::Staleness::Merged => m.status_merged.to_string(),
+        git::Staleness::UpstreamGone => m.status_upstream_gone.to_string(),
+        git::Staleness::Inactive(days) => m.stale_inactive.replacen("%s", &days.to_string(), 1),
+    }
 }
 
-pub fn cmd_switch(app: &App) {
+pub fn cmd_clean(app: &App, args: CleanArgs) {
     require_git_repo(app);
     let m = app.messages;
 
+    if !args.yes && !args.dry_run && !io::stdin().is_terminal() {
+        err(m.clean_not_a_terminal);
+        std::process::exit(EXIT_USAGE);
+    }
+
     let worktrees = git::worktree_list();
+    let main_wt = worktrees
+        .first()
+        .map(|wt| wt.path.clone())
+        .unwrap_or_default();
+    let main_ref = git::main_branch_ref();
+    let current = git::current_worktree(&worktrees);
+    let stale_days = args.days.unwrap_or(DEFAULT_STALE_DAYS);
+    let now = unix_now();
+
+    git::load_statuses(&worktrees, main_ref.as_deref());
 
-    if worktrees.len() <= 1 {
-        info(m.no_wt_to_switch);
+    // Locked worktrees and the one we're standing in are never offered
+    let mut candidates = Vec::new();
+    let mut labels = Vec::new();
+    let mut preselected = Vec::new();
+    for (i, wt) in worktrees.iter().enumerate() {
+        if wt.path == main_wt || wt.bare || wt.locked.is_some() || current == Some(i) {
+            continue;
+        }
+        let status = match wt.status(main_ref.as_deref()) {
+            Some(s) => s,
+            None => continue,
+        };
+        let reasons = status.staleness(now, stale_days);
+        if reasons.is_empty() {
+            continue;
+        }
+
+        // Finished branches with nothing uncommitted are safe to remove
+        let finished = reasons
+            .iter()
+            .any(|r| matches!(r, git::Staleness::Merged | git::Staleness::UpstreamGone));
+        if finished && !status.is_dirty() {
+            preselected.push(candidates.len());
+        }
+
+        let mut notes: Vec<String> = reasons.iter().map(|r| staleness_label(app, *r)).collect();
+        if status.is_dirty() {
+            notes.push(change_counts(status));
+        }
+        labels.push(format!(
+            "{}  ({})  {}",
+            wt.label(),
+            notes.join(", "),
+            wt.path
+        ));
+        candidates.push(wt);
+    }
+
+    let prunable = worktrees.iter().filter(|wt| wt.prunable.is_some()).count();
+
+    if candidates.is_empty() && prunable == 0 {
+        info(m.clean_none);
         return;
     }
 
-    let current_dir = std::env::current_dir()
-        .ok()
-        .and_then(|p| p.canonicalize().ok())
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_default();
+    if args.dry_run {
+        if !candidates.is_empty() {
+            eprintln!("\n{BOLD}{}{NC}", m.clean_would_remove);
+            for (i, label) in labels.iter().enumerate() {
+                let mark = if preselected.contains(&i) { "x" } else { " " };
+                eprintln!("  [{}] {}", mark, label);
+            }
+        }
+        if prunable > 0 {
+            eprintln!();
+            info(&m.clean_would_prune.replacen("%s", &prunable.to_string(), 1));
+        }
+        eprintln!();
+        return;
+    }
+
+    let selected = if candidates.is_empty() || args.yes {
+        preselected
+    } else {
+        match multi_select_menu(m.clean_title, &labels, &preselected, m.multi_menu_hint) {
+            Some(list) => list.checked,
+            None => {
+                info(m.cancelled);
+                std::process::exit(EXIT_CANCELLED);
+            }
+        }
+    };
+
+    let mut failed = false;
+    let mut removed_branches = Vec::new();
+    for &i in &selected {
+        let wt = candidates[i];
+        if !before_remove(app, wt) {
+            failed = true;
+        } else if git::worktree_remove(&wt.path) {
+            success(&format!("{}: {}", m.wt_removed, wt.path));
+            if let Some(branch) = wt.branch_name() {
+                removed_branches.push(branch);
+            }
+        } else {
+            err(&format!("{}: {}", m.wt_remove_failed, wt.path));
+            failed = true;
+        }
+    }
+
+    let delete_branches = !removed_branches.is_empty()
+        && (args.delete_branches
+            || (!args.yes && confirm(&format!("{} [y/N]:", m.clean_delete_branches))));
+    if delete_branches {
+        // Plain `-d` only: anything unmerged is left for `shuu remove` to force
+        for branch in removed_branches {
+            if git::branch_delete(branch) {
+                success(&format!("{}: {}", m.branch_deleted, branch));
+            } else {
+                info(&m.clean_branch_kept.replacen("%s", branch, 1));
+            }
+        }
+    }
+
+    if prunable > 0 && git::worktree_prune() {
+        success(&m.clean_pruned.replacen("%s", &prunable.to_string(), 1));
+    }
+
+    if failed {
+        std::process::exit(EXIT_FAILURE);
+    }
+}
+
+/// Longest the preview spends adding up a worktree's size, so highlighting
+/// one with a huge `node_modules` or `target` doesn't freeze the picker
+const PREVIEW_SIZE_BUDGET: Duration = Duration::from_millis(150);
+
+/// Bytes used by files under `path`, without following symlinks. Stops at
+/// `deadline`, returning the bytes counted so far and false.
+fn dir_size(path: &Path, deadline: Instant) -> (u64, bool) {
+    let entries = match fs::read_dir(path) {
+        Ok(e) => e,
+        Err(_) => return (0, true),
+    };
+    let mut total = 0;
+    for entry in entries.flatten() {
+        if Instant::now() >= deadline {
+            return (total, false);
+        }
+        match entry.metadata() {
+            Ok(md) if md.is_dir() => {
+                let (size, complete) = dir_size(&entry.path(), deadline);
+                total += size;
+                if !complete {
+                    return (total, false);
+                }
+            }
+            Ok(md) => total += md.len(),
+            Err(_) => {}
+        }
+    }
+    (total, true)
+}
+
+/// Lines for the picker preview: tracking state and size, changed files
+/// and the latest commits
+fn worktree_preview(app: &App, wt: &git::Worktree, main_ref: Option<&str>) -> Vec<String> {
+    const MAX_CHANGES: usize = 4;
+    let m = app.messages;
+
+    let mut summary = vec![wt.label().to_string()];
+    if let Some(status) = wt.status(main_ref) {
+        if status.upstream_gone() {
+            summary.push(m.status_upstream_gone.to_string());
+        } else if let (Some(up), Some((ahead, behind))) =
+            (&status.upstream, status.upstream_ahead_behind)
+        {
+            summary.push(format!("{} \u{2191}{} \u{2193}{}", up, ahead, behind));
+        }
+        if let (Some(main), Some((ahead, behind))) = (main_ref, status.main_ahead_behind) {
+            summary.push(format!("{} \u{2191}{} \u{2193}{}", main, ahead, behind));
+        }
+    }
+    let (size, complete) = dir_size(Path::new(&wt.path), Instant::now() + PREVIEW_SIZE_BUDGET);
+    if complete {
+        summary.push(output::human_size(size));
+    } else {
+        summary.push(format!(">{}", output::human_size(size)));
+    }
+
+    let mut lines = vec![summary.join("  \u{b7}  ")];
+    let changes = git::changed_files(&wt.path);
+    if changes.is_empty() {
+        lines.push(m.status_clean.to_string());
+    } else {
+        lines.extend(changes.iter().take(MAX_CHANGES).cloned());
+        if changes.len() > MAX_CHANGES {
+            let more = (changes.len() - MAX_CHANGES).to_string();
+            lines.push(m.report_more.replacen("%s", &more, 1));
+        }
+    }
+    lines.extend(git::recent_commits(&wt.path, 5));
+    lines
+}
+
+/// Picker over switchable worktrees, optionally pre-filtered by `query`
+fn pick_worktree(app: &App, worktrees: &[git::Worktree], query: &str) -> Option<usize> {
+    let m = app.messages;
+    let current = git::current_worktree(worktrees);
 
     let labels: Vec<String> = worktrees
         .iter()
-        .map(|wt| {
-            let mut label = format!("{}  {}", wt.branch, wt.path);
-            if current_dir.starts_with(&wt.path) {
+        .enumerate()
+        .map(|(i, wt)| {
+            let mut label = format!("{}  {}", wt.label(), wt.path);
+            if current == Some(i) {
                 label.push_str(" (current)");
             }
             label
         })
         .collect();
 
-    let selected = match select_menu(m.select_wt_switch, &labels, m.menu_hint) {
+    let main_ref = git::main_branch_ref();
+    let mut preview = Preview::new(|i| worktree_preview(app, &worktrees[i], main_ref.as_deref()));
+    let hint = format!("{}  {}", m.menu_hint, m.hint_preview);
+    select_menu_with_preview(m.select_wt_switch, &labels, &hint, query, &mut preview)
+}
+
+/// The single worktree matching `query`, or the user's pick if several do
+fn find_worktree(app: &App, worktrees: &[git::Worktree], query: &str) -> Option<usize> {
+    let m = app.messages;
+    let matches = git::match_worktrees(query, worktrees);
+
+    match matches.len() {
+        0 => {
+            err(&m.no_wt_matches.replacen("%s", query, 1));
+            std::process::exit(EXIT_FAILURE);
+        }
+        1 => Some(matches[0]),
+        _ if !io::stdin().is_terminal() => {
+            err(&m.wt_ambiguous.replacen("%s", query, 1));
+            for i in matches {
+                eprintln!("  {}  {DIM}{}{NC}", worktrees[i].label(), worktrees[i].path);
+            }
+            std::process::exit(EXIT_FAILURE);
+        }
+        _ => pick_worktree(app, worktrees, query),
+    }
+}
+
+/// Index of the worktree `shuu switch -` goes back to
+fn previous_worktree(app: &App, worktrees: &[git::Worktree]) -> usize {
+    let m = app.messages;
+    let previous = match state::previous_worktree() {
+        Some(p) => p,
+        None => {
+            err(m.no_previous_wt);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+    match worktrees.iter().position(|wt| wt.path == previous) {
+        Some(i) => i,
+        None => {
+            err(&m.previous_wt_gone.replacen("%s", &previous, 1));
+            std::process::exit(EXIT_FAILURE);
+        }
+    }
+}
+
+pub fn cmd_switch(app: &App, query: Option<&str>) {
+    require_git_repo(app);
+    let m = app.messages;
+
+    let all = git::worktree_list();
+    let main_path = all.first().map(|wt| wt.path.clone()).unwrap_or_default();
+
+    // Bare and prunable entries have no checkout to cd into
+    let worktrees: Vec<_> = all
+        .into_iter()
+        .filter(|wt| !wt.bare && wt.prunable.is_none())
+        .collect();
+
+    let selected = match query {
+        Some("-") => Some(previous_worktree(app, &worktrees)),
+        Some("main") if worktrees.first().is_some_and(|wt| wt.path == main_path) => Some(0),
+        Some(q) => find_worktree(app, &worktrees, q),
+        None if worktrees.len() <= 1 => {
+            info(m.no_wt_to_switch);
+            return;
+        }
+        None => pick_worktree(app, &worktrees, ""),
+    };
+    let selected = match selected {
         Some(s) => s,
         None => return,
     };
 
     let target = &worktrees[selected];
-    success(&m.switching_to.replacen("%s", &target.branch, 1));
-    let _ = std::fs::write("/tmp/.shuu_cd_target", &target.path);
+    success(&m.switching_to.replacen("%s", target.label(), 1));
+    let ctx = hooks::Context {
+        path: &target.path,
+        branch: target.branch_name(),
+        base: None,
+    };
+    // A failed post-switch hook is reported but still switches
+    hooks::run(app, Hook::PostSwitch, &ctx);
+    if !hand_off_dir(app, &target.path) {
+        eprintln!(
+            "{CYAN}\u{25b8}{NC} {}",
+            m.cd_hint.replacen("%s", &target.path, 1)
+        );
+        eprintln!("  {DIM}{}{NC}", m.help_note_switch);
+    }
+}
+
+pub fn cmd_init(app: &App, shell_name: Option<&str>) {
+    let m = app.messages;
+
+    let shell_name = match shell_name {
+        Some(s) => s,
+        None => {
+            err(m.init_usage);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+
+    match shell::init_script(shell_name) {
+        Some(script) => print!("{}", script),
+        None => {
+            err(&m.unsupported_shell.replacen("%s", shell_name, 1));
+            eprintln!("{}", m.init_usage);
+            std::process::exit(EXIT_USAGE);
+        }
+    }
 }
 
 pub fn cmd_settings(app: &App) {
     let m = app.messages;
 
-    let current_lang_code = config::get_lang().unwrap_or_default();
+    let current_lang_code = config::get_lang(&app.config).unwrap_or_default();
     let mut lang_display = m.model_none.to_string();
     for (i, &(code, _)) in i18n::LANG_CODES.iter().enumerate() {
         if code == current_lang_code {
@@ -256,11 +1388,16 @@ pub fn cmd_settings(app: &App) {
         }
     }
 
-    let model_display = config::get_model().unwrap_or_else(|| m.model_none.to_string());
+    let backend_display = app.config.get("ai.backend").unwrap_or_default().to_string();
+    let model_display = config::get_model(&app.config).unwrap_or_else(|| m.model_none.to_string());
+
+    let base_display = config::default_base(&app.config).unwrap_or_else(|| "HEAD".to_string());
 
     let items = vec![
         format!("{}  ({})", m.settings_lang, lang_display),
+        format!("{}  ({})", m.settings_backend, backend_display),
         format!("{}  ({})", m.settings_model, model_display),
+        format!("{}  ({})", m.settings_base, base_display),
         m.settings_reset.to_string(),
     ];
 
@@ -271,18 +1408,20 @@ pub fn cmd_settings(app: &App) {
 
     match selected {
         0 => select_lang(app),
-        1 => select_model(app),
-        2 => {
-            config::reset();
-            success(m.settings_reset_done);
-        }
+        1 => select_backend(app, &backend_display),
+        2 => select_model(app, &backend_display),
+        3 => select_default_base(app),
+        4 => match config::reset() {
+            Ok(()) => success(m.settings_reset_done),
+            Err(e) => write_failed(app, &e),
+        },
         _ => {}
     }
 }
 
 fn select_lang(app: &App) {
     let m = app.messages;
-    let current = config::get_lang().unwrap_or_default();
+    let current = config::get_lang(&app.config).unwrap_or_default();
 
     let items: Vec<String> = i18n::LANG_CODES
         .iter()
@@ -302,7 +1441,9 @@ fn select_lang(app: &App) {
     };
 
     let (new_code, new_lang) = i18n::LANG_CODES[selected];
-    config::set_lang(new_code);
+    if let Err(e) = config::set_lang(new_code) {
+        write_failed(app, &e);
+    }
     let new_messages = i18n::messages(new_lang);
     success(
         &new_messages
@@ -311,9 +1452,47 @@ fn select_lang(app: &App) {
     );
 }
 
-fn select_model(app: &App) {
+fn select_backend(app: &App, current: &str) {
     let m = app.messages;
-    let current = config::get_model().unwrap_or_default();
+    let items: Vec<String> = ai::BACKENDS
+        .iter()
+        .map(|&(id, label)| {
+            let mut label = label.to_string();
+            if id == current {
+                label.push_str(" \u{2605}");
+            }
+            label
+        })
+        .collect();
+
+    let selected = match select_menu(m.settings_backend, &items, m.menu_hint) {
+        Some(s) => s,
+        None => return,
+    };
+
+    let (id, _) = ai::BACKENDS[selected];
+    if let Err(e) = config::write(Scope::User, "ai.backend", Some(id)) {
+        write_failed(app, &e);
+    }
+    success(&m.backend_set.replacen("%s", id, 1));
+}
+
+/// Claude models from a list, anything else by name
+fn select_model(app: &App, backend: &str) {
+    let m = app.messages;
+    let current = config::get_model(&app.config).unwrap_or_default();
+
+    if !matches!(backend, "claude-cli" | "anthropic") {
+        let model = read_line_prompt(m.model_prompt);
+        if model.is_empty() {
+            return;
+        }
+        if let Err(e) = config::set_model(&model) {
+            write_failed(app, &e);
+        }
+        success(&m.model_set.replacen("%s", &model, 1));
+        return;
+    }
 
     let model_ids = [
         "claude-sonnet-4-6",
@@ -343,10 +1522,113 @@ fn select_model(app: &App) {
         None => return,
     };
 
-    config::set_model(model_ids[selected]);
+    if let Err(e) = config::set_model(model_ids[selected]) {
+        write_failed(app, &e);
+    }
     success(&m.model_set.replacen("%s", model_ids[selected], 1));
 }
 
+fn select_default_base(app: &App) {
+    require_git_repo(app);
+    let m = app.messages;
+    let current = config::default_base(&app.config);
+
+    let mut bases: Vec<Option<String>> = vec![None];
+    bases.extend(git::base_refs().into_iter().map(Some));
+
+    let items: Vec<String> = bases
+        .iter()
+        .map(|b| {
+            let mut label = b.clone().unwrap_or_else(|| "HEAD".to_string());
+            if *b == current {
+                label.push_str(" \u{2605}");
+            }
+            label
+        })
+        .collect();
+
+    let selected = match select_menu(m.settings_base, &items, m.menu_hint) {
+        Some(s) => s,
+        None => return,
+    };
+
+    let base = bases[selected].as_deref();
+    if let Err(e) = config::set_default_base(base) {
+        write_failed(app, &e);
+    }
+    match base {
+        Some(b) => success(&m.base_set.replacen("%s", b, 1)),
+        None => success(m.base_unset),
+    }
+}
+
+/// Reports a setting that could not be saved and exits
+fn write_failed(app: &App, e: &io::Error) -> ! {
+    err(&app
+        .messages
+        .config_write_error
+        .replacen("%s", &e.to_string(), 1));
+    std::process::exit(EXIT_FAILURE);
+}
+
+/// Where `shuu config set` wrote, for the confirmation message
+fn scope_label(scope: Scope) -> String {
+    match scope {
+        Scope::User => config::user_file().display().to_string(),
+        Scope::Repo => config::REPO_FILE.to_string(),
+        Scope::GitConfig => "git config --local".to_string(),
+    }
+}
+
+pub fn cmd_config(app: &App, action: ConfigAction) {
+    let m = app.messages;
+
+    match action {
+        ConfigAction::Get { key, show_origin } => match app.config.entry(&key) {
+            Some(e) if show_origin => println!("{}\t{}", e.origin_label(), e.value),
+            Some(e) => println!("{}", e.value),
+            None => std::process::exit(EXIT_FAILURE),
+        },
+        ConfigAction::List { show_origin } => {
+            for e in app.config.effective() {
+                if show_origin {
+                    println!("{}\t{}={}", e.origin_label(), e.key, e.value);
+                } else {
+                    println!("{}={}", e.key, e.value);
+                }
+            }
+        }
+        ConfigAction::Set { key, value, scope } => {
+            if !config::is_valid_value(&key, &value) {
+                err(&m
+                    .invalid_value
+                    .replacen("%s", &key, 1)
+                    .replacen("%s", &value, 1));
+                std::process::exit(EXIT_USAGE);
+            }
+            if let Err(e) = config::write(scope, &key, Some(&value)) {
+                write_failed(app, &e);
+            }
+            success(
+                &m.config_set_done
+                    .replacen("%s", &key, 1)
+                    .replacen("%s", &value, 1)
+                    .replacen("%s", &scope_label(scope), 1),
+            );
+        }
+        ConfigAction::Unset { key, scope } => {
+            if let Err(e) = config::write(scope, &key, None) {
+                write_failed(app, &e);
+            }
+            success(&m.config_unset_done.replacen("%s", &key, 1).replacen(
+                "%s",
+                &scope_label(scope),
+                1,
+            ));
+        }
+    }
+}
+
 pub fn cmd_help(app: &App) {
     let m = app.messages;
     eprintln!();
@@ -358,23 +1640,83 @@ pub fn cmd_help(app: &App) {
     eprintln!();
     eprintln!("{BOLD}{}:{NC}", m.help_commands);
     eprintln!("    {GREEN}create{NC}  (c)      {}", m.help_create_desc);
+    eprintln!("    {GREEN}checkout{NC} (co)    {}", m.help_checkout_desc);
     eprintln!("    {GREEN}list{NC}    (l, ls)  {}", m.help_list_desc);
     eprintln!("    {GREEN}remove{NC}  (rm)     {}", m.help_remove_desc);
-    eprintln!("    {GREEN}switch{NC}  (s)      {}", m.help_switch_desc);
+    eprintln!("    {GREEN}clean{NC}   (prune)  {}", m.help_clean_desc);
+    eprintln!("    {GREEN}switch{NC}  (s) [q]  {}", m.help_switch_desc);
     eprintln!("    {GREEN}settings{NC}         {}", m.help_settings_desc);
+    eprintln!(
+        "    {GREEN}config{NC}  [get|set|unset|list]  {}",
+        m.help_config_desc
+    );
+    eprintln!("    {GREEN}init{NC}    <shell>  {}", m.help_init_desc);
     eprintln!("    {GREEN}help{NC}    (-h)     {}", m.help_help_desc);
     eprintln!();
+    eprintln!("{BOLD}{}:{NC}", m.help_options);
+    eprintln!("    {GREEN}-b, --branch{NC} <name>  {}", m.help_opt_branch);
+    eprintln!("    {GREEN}--base{NC} <ref>         {}", m.help_opt_base);
+    eprintln!("    {GREEN}--fetch{NC}              {}", m.help_opt_fetch);
+    eprintln!("    {GREEN}--no-ai{NC}              {}", m.help_opt_no_ai);
+    eprintln!("    {GREEN}-y, --yes{NC}            {}", m.help_opt_yes);
+    eprintln!(
+        "    {GREEN}--show-prompt{NC}        {}",
+        m.help_opt_show_prompt
+    );
+    eprintln!("    {GREEN}--remote{NC} <name>      {}", m.help_opt_remote);
+    eprintln!("    {GREEN}--json{NC}               {}", m.help_opt_json);
+    eprintln!(
+        "    {GREEN}--porcelain{NC}          {}",
+        m.help_opt_porcelain
+    );
+    eprintln!("    {GREEN}--format{NC} <template>  {}", m.help_opt_format);
+    eprintln!("    {GREEN}--days{NC} <n>           {}", m.help_opt_days);
+    eprintln!("    {GREEN}-n, --dry-run{NC}        {}", m.help_opt_dry_run);
+    eprintln!(
+        "    {GREEN}--delete-branches{NC}    {}",
+        m.help_opt_delete_branches
+    );
+    eprintln!(
+        "    {GREEN}--show-origin{NC}        {}",
+        m.help_opt_show_origin
+    );
+    eprintln!(
+        "    {GREEN}--repo, --local{NC}      {}",
+        m.help_opt_config_scope
+    );
+    eprintln!(
+        "    {GREEN}-c{NC} <key>=<value>     {}",
+        m.help_opt_override
+    );
+    eprintln!();
     eprintln!("{BOLD}{}:{NC}", m.help_examples);
     eprintln!("    shuu                      # {}", m.menu_title);
     eprintln!("    shuu create               # {}", m.help_create_desc);
     eprintln!("    shuu \"implement auth\"     # {}", m.help_direct_desc);
+    eprintln!("    shuu co feat/login        # {}", m.help_checkout_desc);
+    eprintln!("    shuu co 123               # {}", m.help_checkout_desc);
     eprintln!("    shuu ls                   # {}", m.help_list_desc);
     eprintln!("    shuu s                    # {}", m.help_switch_desc);
+    eprintln!("    shuu s auth               # {}", m.help_switch_query);
+    eprintln!("    shuu s -                  # {}", m.help_switch_previous);
     eprintln!("    shuu rm                   # {}", m.help_remove_desc);
+    eprintln!("    shuu clean --dry-run      # {}", m.help_opt_dry_run);
+    eprintln!("    shuu c -y --no-ai \"auth\"  # {}", m.help_opt_yes);
+    eprintln!(
+        "    shuu config list --show-origin  # {}",
+        m.help_opt_show_origin
+    );
+    eprintln!("    shuu -c fetch=true c      # {}", m.help_opt_override);
+    eprintln!("    eval \"$(shuu init zsh)\"    # {}", m.help_init_desc);
     eprintln!();
     eprintln!("{BOLD}{}:{NC}", m.help_notes);
-    eprintln!("    {}", m.help_note_path);
+    eprintln!(
+        "    {}",
+        m.help_note_path
+            .replacen("%s", &config::worktree_path_template(&app.config), 1)
+    );
     eprintln!("    {}", m.help_note_switch);
+    eprintln!("    {}", m.help_note_exit);
     eprintln!();
 }
 
@@ -386,6 +1728,7 @@ pub fn cmd_interactive(app: &App) {
         m.menu_create.to_string(),
         m.menu_list.to_string(),
         m.menu_remove.to_string(),
+        m.menu_clean.to_string(),
         m.menu_switch.to_string(),
         m.menu_settings.to_string(),
         m.menu_help.to_string(),
@@ -397,12 +1740,13 @@ pub fn cmd_interactive(app: &App) {
     };
 
     match selected {
-        0 => cmd_create(app, String::new()),
-        1 => cmd_list(app),
+        0 => cmd_create(app, CreateArgs::default()),
+        1 => cmd_list(app, ListFormat::Table),
         2 => cmd_remove(app),
-        3 => cmd_switch(app),
-        4 => cmd_settings(app),
-        5 => cmd_help(app),
+        3 => cmd_clean(app, CleanArgs::default()),
+        4 => cmd_switch(app, None),
+        5 => cmd_settings(app),
+        6 => cmd_help(app),
         _ => {}
     }
 }
@@ -1,8 +1,673 @@
-use crate::i18n::Lang;
+use crate::i18n::{Lang, Messages};
+use crate::{ai, git, glob, template};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml_edit::{DocumentMut, Item, Table, Value};
 
-fn config_dir() -> PathBuf {
+/// Per-repository settings, committed alongside the code
+pub const REPO_FILE: &str = ".shuu.toml";
+
+/// The user's settings, in `config_dir()`
+pub const USER_FILE: &str = "config.toml";
+
+/// Layout version written to `USER_FILE`
+const USER_VERSION: i64 = 1;
+
+/// Settings that decide where prompts and API keys are sent. A cloned
+/// repository's `.shuu.toml` must not redirect them.
+const PERSONAL_KEYS: &[&str] = &["ai.backend", "ai.url", "ai.api_key"];
+
+/// Where worktrees go unless `worktree.path` says otherwise
+pub const DEFAULT_WORKTREE_PATH: &str = "../{repo}-worktrees/{branch_slug}";
+
+/// Settings that older versions kept as one raw file each in `config_dir()`
+const LEGACY_KEYS: &[&str] = &["lang", "model"];
+
+/// Where a setting's value came from, lowest precedence first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    Default,
+    User,
+    Repo,
+    GitConfig,
+    Env,
+    CommandLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Bool,
+    Lang,
+    /// Worktree path template, see `git::compute_worktree_path`
+    PathTemplate,
+    /// Whole number of seconds, at least 1
+    Seconds,
+    /// Whole number from 1 up to the given maximum
+    Count(u64),
+    /// One of a fixed set of words
+    Choice(&'static [&'static str]),
+    /// Glob patterns, a TOML array or separated by commas
+    Patterns,
+    /// Any words, a TOML array or separated by commas
+    List,
+}
+
+/// A setting the layered config knows about
+struct Setting {
+    key: &'static str,
+    kind: Kind,
+    /// Environment variable that overrides files
+    env: Option<&'static str>,
+    default: Option<&'static str>,
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "lang",
+        kind: Kind::Lang,
+        env: Some("SHUU_LANG"),
+        default: None,
+    },
+    Setting {
+        key: "model",
+        kind: Kind::Text,
+        env: Some("SHUU_MODEL"),
+        default: None,
+    },
+    Setting {
+        key: "base",
+        kind: Kind::Text,
+        env: Some("SHUU_DEFAULT_BASE"),
+        default: None,
+    },
+    Setting {
+        key: "fetch",
+        kind: Kind::Bool,
+        env: Some("SHUU_FETCH"),
+        default: Some("false"),
+    },
+    Setting {
+        key: "worktree.path",
+        kind: Kind::PathTemplate,
+        env: None,
+        default: Some(DEFAULT_WORKTREE_PATH),
+    },
+    Setting {
+        key: "hooks.post_create",
+        kind: Kind::Text,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "hooks.pre_remove",
+        kind: Kind::Text,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "hooks.post_switch",
+        kind: Kind::Text,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "hooks.timeout",
+        kind: Kind::Seconds,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "hooks.on_failure",
+        kind: Kind::Choice(&["ask", "keep", "rollback"]),
+        env: None,
+        default: Some("ask"),
+    },
+    Setting {
+        key: "files.copy",
+        kind: Kind::Patterns,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "files.hardlink",
+        kind: Kind::Patterns,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "files.symlink",
+        kind: Kind::Patterns,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "files.clone",
+        kind: Kind::Patterns,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "files.clone_fallback",
+        kind: Kind::Choice(&["copy", "hardlink"]),
+        env: None,
+        default: Some("copy"),
+    },
+    Setting {
+        key: "branch.max_length",
+        kind: Kind::Count(255),
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "branch.prefixes",
+        kind: Kind::List,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "ai.backend",
+        kind: Kind::Choice(&["claude-cli", "anthropic", "openai", "ollama"]),
+        env: Some("SHUU_AI_BACKEND"),
+        default: Some("claude-cli"),
+    },
+    Setting {
+        key: "ai.url",
+        kind: Kind::Text,
+        env: Some("SHUU_AI_URL"),
+        default: None,
+    },
+    Setting {
+        key: "ai.api_key",
+        kind: Kind::Text,
+        env: Some("SHUU_AI_API_KEY"),
+        default: None,
+    },
+    Setting {
+        key: "ai.candidates",
+        kind: Kind::Count(ai::MAX_CANDIDATES),
+        env: None,
+        default: Some("3"),
+    },
+    Setting {
+        key: "ai.instructions",
+        kind: Kind::Text,
+        env: None,
+        default: None,
+    },
+    Setting {
+        key: "ai.prompt_template",
+        kind: Kind::Text,
+        env: Some("SHUU_AI_PROMPT_TEMPLATE"),
+        default: None,
+    },
+];
+
+fn setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.key == key)
+}
+
+pub fn is_known_key(key: &str) -> bool {
+    setting(key).is_some()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether `value` is acceptable for `key`. Unknown keys take nothing.
+pub fn is_valid_value(key: &str, value: &str) -> bool {
+    match setting(key).map(|s| s.kind) {
+        Some(Kind::Text) => !value.trim().is_empty(),
+        Some(Kind::Bool) => parse_bool(value).is_some(),
+        Some(Kind::Lang) => parse_lang(value).is_some(),
+        Some(Kind::PathTemplate) => is_valid_path_template(value),
+        Some(Kind::Seconds) => value.trim().parse::<u64>().is_ok_and(|s| s > 0),
+        Some(Kind::Count(max)) => value
+            .trim()
+            .parse::<u64>()
+            .is_ok_and(|n| (1..=max).contains(&n)),
+        Some(Kind::Choice(words)) => words.contains(&value.trim()),
+        Some(Kind::Patterns) => {
+            let patterns = split_list(value);
+            !patterns.is_empty() && patterns.iter().all(|p| glob::is_valid(p))
+        }
+        Some(Kind::List) => !split_list(value).is_empty(),
+        None => false,
+    }
+}
+
+/// Items of a list setting, which is stored as one comma-separated string
+pub fn split_list(value: &str) -> Vec<String> {
+    value
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Only known placeholders, and at least one naming the branch so two
+/// branches can't share a directory
+fn is_valid_path_template(value: &str) -> bool {
+    match template::placeholders(value) {
+        Ok(names) => {
+            names.iter().all(|n| git::PATH_PLACEHOLDERS.contains(n))
+                && names.iter().any(|n| *n == "branch" || *n == "branch_slug")
+        }
+        Err(_) => false,
+    }
+}
+
+/// One value for a setting from one layer
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub key: &'static str,
+    pub value: String,
+    pub origin: Origin,
+    /// File, variable or git key the value was read from
+    pub source: String,
+}
+
+impl Entry {
+    /// `origin:source`, as shown by `--show-origin`
+    pub fn origin_label(&self) -> String {
+        Entry::label(self.origin, &self.source)
+    }
+
+    fn label(origin: Origin, source: &str) -> String {
+        let origin = match origin {
+            Origin::Default => return "default".to_string(),
+            Origin::User => "user",
+            Origin::Repo => "repo",
+            Origin::GitConfig => "git",
+            Origin::Env => "env",
+            Origin::CommandLine => "command-line",
+        };
+        format!("{}:{}", origin, source)
+    }
+}
+
+/// A problem with one layer. The layer's value is ignored and lower layers
+/// apply instead.
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    /// File that could not be read or parsed, and why
+    Unreadable(String, String),
+    /// Source and the key it set
+    UnknownSetting(String, String),
+    /// Source, key and the value it could not take
+    InvalidValue(String, String, String),
+    /// User file written by a newer shuu, and its version
+    NewerVersion(String, i64),
+    /// Legacy settings that could not be moved into `USER_FILE`, and why
+    MigrationFailed(String, String),
+    /// `.shuu.toml` and a key from `PERSONAL_KEYS` it tried to set
+    PersonalSetting(String, String),
+}
+
+impl Warning {
+    pub fn describe(&self, m: &Messages) -> String {
+        match self {
+            Warning::Unreadable(source, reason) => format!("{}: {}", source, reason),
+            Warning::UnknownSetting(source, key) => {
+                format!("{}: {}", source, m.unknown_setting.replacen("%s", key, 1))
+            }
+            Warning::InvalidValue(source, key, value) => format!(
+                "{}: {}",
+                source,
+                m.invalid_value
+                    .replacen("%s", key, 1)
+                    .replacen("%s", value, 1)
+            ),
+            Warning::NewerVersion(source, version) => m
+                .config_newer_version
+                .replacen("%s", source, 1)
+                .replacen("%s", &version.to_string(), 1),
+            Warning::MigrationFailed(source, reason) => m
+                .config_migration_failed
+                .replacen("%s", source, 1)
+                .replacen("%s", reason, 1),
+            Warning::PersonalSetting(source, key) => format!(
+                "{}: {}",
+                source,
+                m.config_personal_setting.replacen("%s", key, 1)
+            ),
+        }
+    }
+}
+
+static OVERRIDES: OnceLock<crate::cli::Overrides> = OnceLock::new();
+
+/// `-c key=value` pairs from the command line, the top layer
+pub fn set_overrides(overrides: crate::cli::Overrides) {
+    let _ = OVERRIDES.set(overrides);
+}
+
+/// All settings from every layer: built-in defaults, the user config, the
+/// repository's `.shuu.toml`, `git config shuu.*`, environment variables and
+/// `-c` flags. Later layers win.
+pub struct Config {
+    entries: Vec<Entry>,
+    /// Problems found while reading the layers, for the user to fix
+    pub warnings: Vec<Warning>,
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let mut config = Config {
+            entries: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        for s in SETTINGS {
+            if let Some(default) = s.default {
+                config.push(s.key, default.to_string(), Origin::Default, String::new());
+            }
+        }
+
+        let dir = config_dir();
+        if let Err(e) = migrate_legacy(&dir) {
+            config.warnings.push(Warning::MigrationFailed(
+                user_file().display().to_string(),
+                e.to_string(),
+            ));
+            // Still honour the old files until the migration succeeds
+            for s in SETTINGS.iter().filter(|s| LEGACY_KEYS.contains(&s.key)) {
+                let path = dir.join(s.key);
+                if let Ok(value) = fs::read_to_string(&path) {
+                    config.push(s.key, value, Origin::User, path.display().to_string());
+                }
+            }
+        }
+        config.load_file(&user_file(), Origin::User);
+
+        if let Some(path) = repo_file() {
+            config.load_file(&path, Origin::Repo);
+        }
+
+        for (name, value) in git::config_get_regexp("^shuu\\.") {
+            let key = name.trim_start_matches("shuu.");
+            if let Some(s) = setting(key) {
+                config.push(s.key, value, Origin::GitConfig, name.clone());
+            }
+        }
+
+        for s in SETTINGS {
+            if let Some(var) = s.env {
+                if let Ok(value) = std::env::var(var) {
+                    config.push(s.key, value, Origin::Env, var.to_string());
+                }
+            }
+        }
+
+        for (key, value) in OVERRIDES.get().into_iter().flatten() {
+            if let Some(s) = setting(key) {
+                config.push(s.key, value.clone(), Origin::CommandLine, key.clone());
+            }
+        }
+
+        config
+    }
+
+    /// Adds a layer's value, skipping it with a warning if the setting
+    /// can't take it so lower layers still apply
+    fn push(&mut self, key: &'static str, value: String, origin: Origin, source: String) {
+        let entry = Entry {
+            key,
+            value: value.trim().to_string(),
+            origin,
+            source,
+        };
+        if is_valid_value(key, &entry.value) {
+            self.entries.push(entry);
+        } else if origin != Origin::Env {
+            // Stray environment variables are common enough to stay quiet
+            self.warnings.push(Warning::InvalidValue(
+                entry.origin_label(),
+                key.to_string(),
+                entry.value,
+            ));
+        }
+    }
+
+    /// Reads one TOML layer. A missing file is an empty layer.
+    fn load_file(&mut self, path: &Path, origin: Origin) {
+        let source = path.display().to_string();
+        match fs::read_to_string(path) {
+            Ok(content) => self.load_toml(&content, &source, origin),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => self
+                .warnings
+                .push(Warning::Unreadable(source, e.to_string())),
+        }
+    }
+
+    fn load_toml(&mut self, content: &str, path: &str, origin: Origin) {
+        let doc = match content.parse::<DocumentMut>() {
+            Ok(doc) => doc,
+            Err(e) => {
+                self.warnings.push(Warning::Unreadable(
+                    path.to_string(),
+                    parse_error(content, &e),
+                ));
+                return;
+            }
+        };
+
+        let mut values = Vec::new();
+        flatten_table(doc.as_table(), "", &mut values);
+        for (key, value) in values {
+            if origin == Origin::User && key == "version" {
+                match value.parse::<i64>() {
+                    Ok(v) if v <= USER_VERSION => {}
+                    Ok(v) => self
+                        .warnings
+                        .push(Warning::NewerVersion(path.to_string(), v)),
+                    Err(_) => self.warnings.push(Warning::InvalidValue(
+                        Entry::label(origin, path),
+                        key,
+                        value,
+                    )),
+                }
+                continue;
+            }
+            match setting(&key) {
+                Some(s) if origin == Origin::Repo && PERSONAL_KEYS.contains(&s.key) => self
+                    .warnings
+                    .push(Warning::PersonalSetting(Entry::label(origin, path), key)),
+                Some(s) => self.push(s.key, value, origin, path.to_string()),
+                None => self
+                    .warnings
+                    .push(Warning::UnknownSetting(Entry::label(origin, path), key)),
+            }
+        }
+    }
+
+    /// The value in effect for `key`, with where it came from
+    pub fn entry(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().rev().find(|e| e.key == key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(|e| e.value.as_str())
+    }
+
+    pub fn get_bool(&self, key: &str) -> bool {
+        self.get(key).and_then(parse_bool).unwrap_or(false)
+    }
+
+    /// The effective entry of every setting that has a value, in a fixed order
+    pub fn effective(&self) -> Vec<&Entry> {
+        SETTINGS.iter().filter_map(|s| self.entry(s.key)).collect()
+    }
+}
+
+/// `line N: message` on one line, for warnings
+fn parse_error(content: &str, e: &toml_edit::TomlError) -> String {
+    let line = e
+        .span()
+        .map(|span| content[..span.start].matches('\n').count() + 1)
+        .unwrap_or(1);
+    let message: Vec<&str> = e.message().lines().map(str::trim).collect();
+    format!("line {}: {}", line, message.join("; "))
+}
+
+/// Collects `dotted.key = value` pairs from nested TOML tables
+fn flatten_table(table: &Table, prefix: &str, out: &mut Vec<(String, String)>) {
+    for (name, item) in table.iter() {
+        let key = if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match item {
+            Item::Table(t) => flatten_table(t, &key, out),
+            Item::Value(Value::InlineTable(t)) => flatten_table(&t.clone().into_table(), &key, out),
+            Item::Value(Value::String(s)) => out.push((key, s.value().clone())),
+            Item::Value(Value::Array(items)) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|v| match v {
+                        Value::String(s) => s.value().clone(),
+                        v => v.to_string().trim().to_string(),
+                    })
+                    .collect();
+                out.push((key, items.join(", ")));
+            }
+            Item::Value(v) => out.push((key, v.to_string().trim().to_string())),
+            _ => {}
+        }
+    }
+}
+
+/// `.shuu.toml` at the top of the current worktree
+pub fn repo_file() -> Option<PathBuf> {
+    git::toplevel().map(|top| PathBuf::from(top).join(REPO_FILE))
+}
+
+/// Which layer `shuu config set` and `unset` write to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    User,
+    Repo,
+    GitConfig,
+}
+
+/// Sets or, with None, removes `key` in one writable layer
+pub fn write(scope: Scope, key: &str, value: Option<&str>) -> io::Result<()> {
+    let setting = setting(key)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unknown setting"))?;
+    match scope {
+        Scope::User => {
+            migrate_legacy(&config_dir())?;
+            edit_file(&user_file(), setting, value, Some(USER_VERSION))
+        }
+        Scope::Repo if PERSONAL_KEYS.contains(&setting.key) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is a personal setting", setting.key),
+        )),
+        Scope::Repo => {
+            let path = repo_file().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "not in a git repository")
+            })?;
+            edit_file(&path, setting, value, None)
+        }
+        Scope::GitConfig => {
+            let name = format!("shuu.{}", key);
+            // Unsetting a key that isn't there is not a failure
+            if git::config_set_local(&name, value) || value.is_none() {
+                Ok(())
+            } else {
+                Err(io::Error::other(format!("git config {} failed", name)))
+            }
+        }
+    }
+}
+
+/// Sets or removes one key in a TOML file, keeping its comments and layout.
+/// With a `version`, new files start with it.
+fn edit_file(
+    path: &Path,
+    setting: &Setting,
+    value: Option<&str>,
+    version: Option<i64>,
+) -> io::Result<()> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut doc = content.parse::<DocumentMut>().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), parse_error(&content, &e)),
+        )
+    })?;
+    if let Some(version) = version {
+        if !doc.contains_key("version") {
+            doc.insert("version", toml_edit::value(version));
+        }
+    }
+
+    let mut parts: Vec<&str> = setting.key.split('.').collect();
+    let name = parts.pop().unwrap_or(setting.key);
+    let mut table = doc.as_table_mut();
+    for part in parts {
+        let item = table.entry(part).or_insert_with(|| {
+            let mut t = Table::new();
+            t.set_implicit(true);
+            Item::Table(t)
+        });
+        table = item.as_table_mut().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a table", part),
+            )
+        })?;
+    }
+
+    match value {
+        Some(v) if setting.kind == Kind::Bool => {
+            table.insert(name, toml_edit::value(parse_bool(v).unwrap_or(false)));
+        }
+        Some(v) if matches!(setting.kind, Kind::Seconds | Kind::Count(_)) => {
+            table.insert(name, toml_edit::value(v.trim().parse::<i64>().unwrap_or(0)));
+        }
+        Some(v) if matches!(setting.kind, Kind::Patterns | Kind::List) => {
+            let items: toml_edit::Array = split_list(v).into_iter().collect();
+            table.insert(name, toml_edit::value(items));
+        }
+        Some(v) => {
+            table.insert(name, toml_edit::value(v));
+        }
+        None => {
+            table.remove(name);
+        }
+    }
+    write_atomic(path, &doc.to_string())
+}
+
+/// Writes through a temporary file so a failed write leaves the old
+/// contents in place
+fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("toml.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+pub fn config_dir() -> PathBuf {
     if let Ok(xdg) = std::env::var("XDG_CONFIG_HOME") {
         return PathBuf::from(xdg).join("shuu");
     }
@@ -10,32 +675,101 @@ fn config_dir() -> PathBuf {
     home.join(".config").join("shuu")
 }
 
-pub fn get_lang() -> Option<String> {
-    fs::read_to_string(config_dir().join("lang")).ok()
+pub fn user_file() -> PathBuf {
+    config_dir().join(USER_FILE)
 }
 
-pub fn set_lang(lang: &str) {
-    let dir = config_dir();
-    let _ = fs::create_dir_all(&dir);
-    let _ = fs::write(dir.join("lang"), lang);
+/// Moves settings from the one-file-per-key layout into `USER_FILE`, then
+/// removes the old files. Does nothing once `USER_FILE` exists.
+fn migrate_legacy(dir: &Path) -> io::Result<()> {
+    let path = dir.join(USER_FILE);
+    if path.exists() {
+        return Ok(());
+    }
+    let legacy: Vec<(&str, String)> = LEGACY_KEYS
+        .iter()
+        .filter_map(|&key| {
+            let value = fs::read_to_string(dir.join(key)).ok()?;
+            Some((key, value.trim().to_string()))
+        })
+        .collect();
+    if legacy.is_empty() {
+        return Ok(());
+    }
+
+    let mut doc = DocumentMut::new();
+    doc.insert("version", toml_edit::value(USER_VERSION));
+    for (key, value) in &legacy {
+        // Empty files meant "not set"
+        if !value.is_empty() {
+            doc.insert(key, toml_edit::value(value.as_str()));
+        }
+    }
+    write_atomic(&path, &doc.to_string())?;
+
+    for (key, _) in &legacy {
+        fs::remove_file(dir.join(key))?;
+    }
+    Ok(())
 }
 
-pub fn get_model() -> Option<String> {
-    fs::read_to_string(config_dir().join("model"))
-        .ok()
-        .filter(|s| !s.is_empty())
+pub fn get_lang(config: &Config) -> Option<String> {
+    config.get("lang").map(|s| s.to_string())
 }
 
-pub fn set_model(model: &str) {
-    let dir = config_dir();
-    let _ = fs::create_dir_all(&dir);
-    let _ = fs::write(dir.join("model"), model);
+pub fn set_lang(lang: &str) -> io::Result<()> {
+    write(Scope::User, "lang", Some(lang))
+}
+
+pub fn get_model(config: &Config) -> Option<String> {
+    config.get("model").map(|s| s.to_string())
 }
 
-pub fn reset() {
+pub fn set_model(model: &str) -> io::Result<()> {
+    write(Scope::User, "model", Some(model))
+}
+
+/// Removes every user setting, including any left in the legacy layout
+pub fn reset() -> io::Result<()> {
     let dir = config_dir();
-    let _ = fs::remove_file(dir.join("lang"));
-    let _ = fs::remove_file(dir.join("model"));
+    for name in LEGACY_KEYS.iter().chain(&[USER_FILE]) {
+        match fs::remove_file(dir.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Default start point for new branches (`base`)
+pub fn default_base(config: &Config) -> Option<String> {
+    config.get("base").map(|s| s.to_string())
+}
+
+/// Stores the default base for this clone only, in `git config shuu.base`
+pub fn set_default_base(base: Option<&str>) -> io::Result<()> {
+    write(Scope::GitConfig, "base", base)
+}
+
+/// Whether to fetch a remote base before branching (`fetch`)
+pub fn fetch_base(config: &Config) -> bool {
+    config.get_bool("fetch")
+}
+
+/// Template for new worktree directories (`worktree.path`)
+pub fn worktree_path_template(config: &Config) -> String {
+    config
+        .get("worktree.path")
+        .unwrap_or(DEFAULT_WORKTREE_PATH)
+        .to_string()
+}
+
+/// How many branch names to ask the AI for at once
+pub fn ai_candidates(config: &Config) -> usize {
+    config
+        .get("ai.candidates")
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1)
 }
 
 fn parse_lang(s: &str) -> Option<Lang> {
@@ -49,6 +783,7 @@ fn parse_lang(s: &str) -> Option<Lang> {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
     use std::sync::Mutex;
@@ -92,6 +827,124 @@ mod tests {
         assert_eq!(parse_lang("ja\n"), Some(Lang::Ja));
     }
 
+    fn empty() -> Config {
+        Config {
+            entries: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_flatten_nested_tables() {
+        let doc: DocumentMut =
+            "base = \"origin/main\"\nfetch = true\n\n[ai]\ninstructions = \"x\"\n"
+                .parse()
+                .unwrap();
+        let mut values = Vec::new();
+        flatten_table(doc.as_table(), "", &mut values);
+        assert_eq!(
+            values,
+            vec![
+                ("base".to_string(), "origin/main".to_string()),
+                ("fetch".to_string(), "true".to_string()),
+                ("ai.instructions".to_string(), "x".to_string()),
+            ]
+        );
+
+        let doc: DocumentMut = "ai = { instructions = \"y\" }".parse().unwrap();
+        let mut values = Vec::new();
+        flatten_table(doc.as_table(), "", &mut values);
+        assert_eq!(
+            values,
+            vec![("ai.instructions".to_string(), "y".to_string())]
+        );
+
+        let doc: DocumentMut = "[files]\ncopy = [\".env\", \".idea/**\"]".parse().unwrap();
+        let mut values = Vec::new();
+        flatten_table(doc.as_table(), "", &mut values);
+        assert_eq!(
+            values,
+            vec![("files.copy".to_string(), ".env, .idea/**".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_later_layers_win() {
+        let mut config = empty();
+        config.push("base", "main".into(), Origin::User, "u".into());
+        config.load_toml("base = \"origin/develop\"", ".shuu.toml", Origin::Repo);
+        assert_eq!(config.get("base"), Some("origin/develop"));
+        assert_eq!(config.entry("base").unwrap().origin, Origin::Repo);
+
+        config.push("base", "v2".into(), Origin::CommandLine, "base".into());
+        assert_eq!(config.get("base"), Some("v2"));
+        assert_eq!(
+            config.entry("base").unwrap().origin_label(),
+            "command-line:base"
+        );
+    }
+
+    #[test]
+    fn test_invalid_values_fall_through() {
+        let mut config = empty();
+        config.push("fetch", "true".into(), Origin::User, "u".into());
+        config.load_toml("fetch = \"sometimes\"\ncolour = 1", "r", Origin::Repo);
+        assert!(config.get_bool("fetch"));
+        assert_eq!(
+            config.warnings,
+            vec![
+                Warning::InvalidValue(
+                    "repo:r".to_string(),
+                    "fetch".to_string(),
+                    "sometimes".to_string()
+                ),
+                Warning::UnknownSetting("repo:r".to_string(), "colour".to_string()),
+            ]
+        );
+
+        config.push("lang", "xx".into(), Origin::Env, "SHUU_LANG".into());
+        assert!(config.get("lang").is_none());
+        assert_eq!(config.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_repo_file_parse_error() {
+        let mut config = empty();
+        config.load_toml("fetch = true\nbase = ", "r", Origin::Repo);
+        assert!(
+            matches!(&config.warnings[..], [Warning::Unreadable(p, e)] if p == "r" && e.starts_with("line 2: "))
+        );
+    }
+
+    #[test]
+    fn test_is_valid_value() {
+        assert!(is_valid_value("fetch", "yes"));
+        assert!(!is_valid_value("fetch", "maybe"));
+        assert!(is_valid_value("lang", "ja"));
+        assert!(!is_valid_value("lang", "klingon"));
+        assert!(!is_valid_value("base", " "));
+        assert!(!is_valid_value("colour", "red"));
+        assert!(is_valid_value("worktree.path", DEFAULT_WORKTREE_PATH));
+        assert!(is_valid_value(
+            "worktree.path",
+            "~/wt/{repo}/{date}-{branch}"
+        ));
+        assert!(!is_valid_value("worktree.path", "~/wt/{repo}"));
+        assert!(!is_valid_value("worktree.path", "{branch}/{bogus}"));
+        assert!(!is_valid_value("worktree.path", "{branch"));
+        assert!(is_valid_value("hooks.timeout", "60"));
+        assert!(!is_valid_value("hooks.timeout", "0"));
+        assert!(!is_valid_value("hooks.timeout", "1m"));
+        assert!(is_valid_value("hooks.on_failure", "rollback"));
+        assert!(!is_valid_value("hooks.on_failure", "retry"));
+        assert!(is_valid_value("ai.candidates", "5"));
+        assert!(!is_valid_value("ai.candidates", "0"));
+        assert!(!is_valid_value("ai.candidates", "6"));
+        assert!(is_valid_value("files.copy", ".env*, .idea/**"));
+        assert!(!is_valid_value("files.copy", ".env, ../secrets"));
+        assert!(!is_valid_value("files.symlink", " , "));
+    }
+
     #[test]
     fn test_parse_lang_invalid() {
         assert_eq!(parse_lang("xx"), None);
@@ -102,18 +955,18 @@ mod tests {
     #[test]
     fn test_set_and_get_lang() {
         with_temp_config(|| {
-            assert!(get_lang().is_none());
-            set_lang("ja");
-            assert_eq!(get_lang().unwrap(), "ja");
+            assert!(get_lang(&Config::load()).is_none());
+            set_lang("ja").unwrap();
+            assert_eq!(get_lang(&Config::load()).unwrap(), "ja");
         });
     }
 
     #[test]
     fn test_set_and_get_model() {
         with_temp_config(|| {
-            assert!(get_model().is_none());
-            set_model("claude-sonnet-4-6");
-            assert_eq!(get_model().unwrap(), "claude-sonnet-4-6");
+            assert!(get_model(&Config::load()).is_none());
+            set_model("claude-sonnet-4-6").unwrap();
+            assert_eq!(get_model(&Config::load()).unwrap(), "claude-sonnet-4-6");
         });
     }
 
@@ -123,20 +976,121 @@ mod tests {
             let dir = config_dir();
             let _ = std::fs::create_dir_all(&dir);
             let _ = std::fs::write(dir.join("model"), "");
-            assert!(get_model().is_none());
+            assert!(get_model(&Config::load()).is_none());
         });
     }
 
     #[test]
     fn test_reset_clears_config() {
         with_temp_config(|| {
-            set_lang("ja");
-            set_model("claude-opus-4-6");
-            assert!(get_lang().is_some());
-            assert!(get_model().is_some());
-            reset();
-            assert!(get_lang().is_none());
-            assert!(get_model().is_none());
+            set_lang("ja").unwrap();
+            set_model("claude-opus-4-6").unwrap();
+            assert!(get_lang(&Config::load()).is_some());
+            assert!(get_model(&Config::load()).is_some());
+            reset().unwrap();
+            assert!(get_lang(&Config::load()).is_none());
+            assert!(get_model(&Config::load()).is_none());
+        });
+    }
+
+    #[test]
+    fn test_migrates_legacy_files() {
+        with_temp_config(|| {
+            let dir = config_dir();
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("lang"), "ja\n").unwrap();
+            std::fs::write(dir.join("model"), "").unwrap();
+
+            assert_eq!(get_lang(&Config::load()).as_deref(), Some("ja"));
+            assert!(!dir.join("lang").exists());
+            assert!(!dir.join("model").exists());
+            assert_eq!(
+                std::fs::read_to_string(user_file()).unwrap(),
+                "version = 1\nlang = \"ja\"\n"
+            );
+        });
+    }
+
+    #[test]
+    fn test_set_writes_versioned_file_and_keeps_comments() {
+        with_temp_config(|| {
+            std::fs::create_dir_all(config_dir()).unwrap();
+            std::fs::write(
+                user_file(),
+                "# dropped with model\nmodel = \"x\"\n# my base\nbase = \"main\"\n",
+            )
+            .unwrap();
+            set_lang("fr").unwrap();
+            write(Scope::User, "model", None).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(user_file()).unwrap(),
+                "# my base\nbase = \"main\"\nversion = 1\nlang = \"fr\"\n"
+            );
+
+            reset().unwrap();
+            set_model("m").unwrap();
+            assert_eq!(
+                std::fs::read_to_string(user_file()).unwrap(),
+                "version = 1\nmodel = \"m\"\n"
+            );
+        });
+    }
+
+    #[test]
+    fn test_patterns_are_written_as_array() {
+        with_temp_config(|| {
+            write(Scope::User, "files.copy", Some(".env*, .idea/**")).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(user_file()).unwrap(),
+                "version = 1\n\n[files]\ncopy = [\".env*\", \".idea/**\"]\n"
+            );
+            let config = Config::load();
+            assert_eq!(config.get("files.copy"), Some(".env*, .idea/**"));
+        });
+    }
+
+    #[test]
+    fn test_repo_file_cannot_redirect_ai() {
+        let mut config = empty();
+        config.load_toml(
+            "[ai]\nurl = \"https://evil.example\"\ninstructions = \"x\"",
+            ".shuu.toml",
+            Origin::Repo,
+        );
+        assert_eq!(config.get("ai.url"), None);
+        assert_eq!(config.get("ai.instructions"), Some("x"));
+        assert_eq!(
+            config.warnings,
+            vec![Warning::PersonalSetting(
+                "repo:.shuu.toml".into(),
+                "ai.url".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_user_file_warnings() {
+        let mut config = empty();
+        config.load_toml("version = 2\nlang = \"ja\"", "u", Origin::User);
+        assert_eq!(config.warnings, vec![Warning::NewerVersion("u".into(), 2)]);
+        assert_eq!(config.get("lang"), Some("ja"));
+
+        let mut config = empty();
+        config.load_toml("lang = \"ja\"\n\n[ai\n", "u", Origin::User);
+        assert!(
+            matches!(&config.warnings[..], [Warning::Unreadable(_, e)] if e.starts_with("line 3: "))
+        );
+        assert!(config.get("lang").is_none());
+    }
+
+    #[test]
+    fn test_write_error_is_reported() {
+        with_temp_config(|| {
+            // A file where the config directory should be
+            let dir = config_dir();
+            std::fs::create_dir_all(dir.parent().unwrap()).unwrap();
+            std::fs::write(&dir, "").unwrap();
+            assert!(set_lang("ja").is_err());
         });
     }
 
@@ -147,7 +1101,7 @@ mod tests {
             let saved_lang = std::env::var("LANG").ok();
             std::env::remove_var("LANG");
 
-            let result = resolve_lang();
+            let result = resolve_lang(&Config::load());
             assert_eq!(result, Lang::En);
 
             if let Some(l) = saved_lang {
@@ -160,7 +1114,7 @@ mod tests {
     fn test_resolve_lang_shuu_lang_env() {
         with_temp_config(|| {
             std::env::set_var("SHUU_LANG", "ja");
-            let result = resolve_lang();
+            let result = resolve_lang(&Config::load());
             assert_eq!(result, Lang::Ja);
             std::env::remove_var("SHUU_LANG");
         });
@@ -173,8 +1127,8 @@ mod tests {
             let saved_lang = std::env::var("LANG").ok();
             std::env::remove_var("LANG");
 
-            set_lang("fr");
-            let result = resolve_lang();
+            set_lang("fr").unwrap();
+            let result = resolve_lang(&Config::load());
             assert_eq!(result, Lang::Fr);
 
             if let Some(l) = saved_lang {
@@ -186,24 +1140,18 @@ mod tests {
     #[test]
     fn test_resolve_lang_priority_shuu_over_config() {
         with_temp_config(|| {
-            set_lang("fr");
+            set_lang("fr").unwrap();
             std::env::set_var("SHUU_LANG", "ja");
-            let result = resolve_lang();
+            let result = resolve_lang(&Config::load());
             assert_eq!(result, Lang::Ja);
             std::env::remove_var("SHUU_LANG");
         });
     }
 }
 
-pub fn resolve_lang() -> Lang {
-    // Priority: SHUU_LANG env > config file > LANG env > English
-    if let Ok(env_lang) = std::env::var("SHUU_LANG") {
-        if let Some(lang) = parse_lang(&env_lang) {
-            return lang;
-        }
-    }
-
-    if let Some(config_lang) = get_lang() {
+pub fn resolve_lang(config: &Config) -> Lang {
+    // Priority: the `lang` setting (SHUU_LANG, -c, files) > LANG env > English
+    if let Some(config_lang) = get_lang(config) {
         if let Some(lang) = parse_lang(&config_lang) {
             return lang;
         }
@@ -220,9 +1168,16 @@ pub fn resolve_lang() -> Lang {
     Lang::En
 }
 
-pub fn check_first_run() {
-    if config_dir().join("lang").exists() {
-        return;
+/// Asks for a language and model on the very first run. Returns true if
+/// settings were written, so `config` is out of date.
+pub fn check_first_run(config: &Config) -> bool {
+    // Scripts and CI get the defaults instead of a setup menu
+    let chosen = config
+        .entries
+        .iter()
+        .any(|e| e.key == "lang" && e.origin == Origin::User);
+    if chosen || !std::io::stdin().is_terminal() {
+        return false;
     }
 
     // Language selection
@@ -239,12 +1194,21 @@ pub fn check_first_run() {
         None => std::process::exit(0),
     };
 
-    let chosen_code = crate::i18n::LANG_CODES[selected].0;
-    set_lang(chosen_code);
-
     // Model selection (now in chosen language)
-    let lang = crate::i18n::LANG_CODES[selected].1;
+    let (chosen_code, lang) = crate::i18n::LANG_CODES[selected];
     let messages = crate::i18n::messages(lang);
+    let report = |result: io::Result<()>| match result {
+        Ok(()) => true,
+        Err(e) => {
+            crate::err(
+                &messages
+                    .config_write_error
+                    .replacen("%s", &e.to_string(), 1),
+            );
+            false
+        }
+    };
+    report(set_lang(chosen_code));
 
     let model_ids = [
         "claude-sonnet-4-6",
@@ -263,11 +1227,13 @@ pub fn check_first_run() {
             None => std::process::exit(0),
         };
 
-    set_model(model_ids[model_selected]);
-    crate::success(
-        &messages
-            .model_set
-            .replacen("%s", model_ids[model_selected], 1),
-    );
+    if report(set_model(model_ids[model_selected])) {
+        crate::success(
+            &messages
+                .model_set
+                .replacen("%s", model_ids[model_selected], 1),
+        );
+    }
     eprintln!();
+    true
 }
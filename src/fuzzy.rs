@@ -0,0 +1,99 @@
+/// Scores `candidate` against `query` as a case-insensitive subsequence.
+/// Returns None if not every query character appears in order. Higher is
+/// better: consecutive matches and matches at word starts score extra.
+pub fn score(query: &str, candidate: &str) -> Option<i32> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let chars: Vec<char> = candidate.to_lowercase().chars().collect();
+
+    // Greedy matching from each possible first character; keep the best
+    let best = (0..chars.len())
+        .filter(|&start| chars[start] == query[0])
+        .filter_map(|start| score_from(&query, &chars, start))
+        .max()?;
+
+    // Prefer shorter candidates among equal matches
+    Some(best * 100 - chars.len() as i32)
+}
+
+fn score_from(query: &[char], chars: &[char], start: usize) -> Option<i32> {
+    let mut total = 0;
+    let mut qi = 0;
+    let mut prev_match: Option<usize> = None;
+
+    for (ci, &c) in chars.iter().enumerate().skip(start) {
+        if qi == query.len() {
+            break;
+        }
+        if c != query[qi] {
+            continue;
+        }
+
+        total += 1;
+        if prev_match.is_some_and(|p| p + 1 == ci) {
+            total += 5;
+        }
+        if ci == 0 || matches!(chars[ci - 1], '/' | '-' | '_' | '.' | ' ') {
+            total += 3;
+        }
+        prev_match = Some(ci);
+        qi += 1;
+    }
+
+    (qi == query.len()).then_some(total)
+}
+
+/// Indices of `items` matching `query`, best first. Ties keep input order.
+pub fn filter<S: AsRef<str>>(query: &str, items: &[S]) -> Vec<usize> {
+    let mut scored: Vec<(usize, i32)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| score(query, item.as_ref()).map(|s| (i, s)))
+        .collect();
+    scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_subsequence() {
+        assert!(score("fln", "feat/login").is_some());
+        assert!(score("FEAT", "feat/login").is_some());
+        assert!(score("nlf", "feat/login").is_none());
+        assert!(score("xyz", "feat/login").is_none());
+    }
+
+    #[test]
+    fn test_score_empty_query_matches_everything() {
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn test_score_prefers_consecutive() {
+        let tight = score("auth", "origin/feat/auth").unwrap();
+        let loose = score("auth", "origin/a-u-t-h").unwrap();
+        assert!(tight > loose);
+    }
+
+    #[test]
+    fn test_filter_orders_by_score() {
+        let items = [
+            "origin/fix/typo",
+            "origin/feat/auth",
+            "origin/feat/a-u-t-h-x",
+        ];
+        assert_eq!(filter("auth", &items), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_filter_empty_query_keeps_order() {
+        let items = ["b", "a", "c"];
+        assert_eq!(filter("", &items), vec![0, 1, 2]);
+    }
+}
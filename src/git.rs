@@ -1,9 +1,366 @@
+use crate::{fuzzy, template};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 
+#[derive(Debug, Default, PartialEq)]
 pub struct Worktree {
     pub path: String,
-    pub hash: String,
-    pub branch: String,
+    /// Full commit sha; empty for bare worktrees
+    pub head: String,
+    /// Full ref, e.g. `refs/heads/main`; None when detached or bare
+    pub branch: Option<String>,
+    pub detached: bool,
+    pub bare: bool,
+    /// Some(reason) if locked; the reason may be empty
+    pub locked: Option<String>,
+    /// Some(reason) if git considers the worktree prunable
+    pub prunable: Option<String>,
+    /// Computed on first use by `status()`
+    status: OnceLock<Option<WorktreeStatus>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WorktreeStatus {
+    /// Staged, unstaged and conflicted paths
+    pub modified: usize,
+    pub untracked: usize,
+    /// Configured upstream, e.g. `origin/feat/x`
+    pub upstream: Option<String>,
+    /// (ahead, behind) the upstream; None without one or if it is gone
+    pub upstream_ahead_behind: Option<(usize, usize)>,
+    /// (ahead, behind) the main branch
+    pub main_ahead_behind: Option<(usize, usize)>,
+    pub last_commit_subject: String,
+    /// Unix time of the last commit
+    pub last_commit_time: i64,
+}
+
+impl WorktreeStatus {
+    pub fn is_dirty(&self) -> bool {
+        self.modified > 0 || self.untracked > 0
+    }
+
+    /// An upstream is configured but the remote branch no longer exists
+    pub fn upstream_gone(&self) -> bool {
+        self.upstream.is_some() && self.upstream_ahead_behind.is_none()
+    }
+
+    /// No commits beyond the main branch, as with `git branch --merged`. A
+    /// branch still sitting on the main branch's tip is new rather than merged.
+    pub fn merged(&self) -> bool {
+        self.main_ahead_behind
+            .is_some_and(|(ahead, behind)| ahead == 0 && behind > 0)
+    }
+
+    /// Why `shuu clean` would offer this worktree for removal
+    pub fn staleness(&self, now: i64, stale_days: u64) -> Vec<Staleness> {
+        let mut reasons = Vec::new();
+        if self.merged() {
+            reasons.push(Staleness::Merged);
+        }
+        if self.upstream_gone() {
+            reasons.push(Staleness::UpstreamGone);
+        }
+        if self.last_commit_time > 0 {
+            let idle_days = (now - self.last_commit_time).max(0) as u64 / 86400;
+            if idle_days >= stale_days {
+                reasons.push(Staleness::Inactive(idle_days));
+            }
+        }
+        reasons
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Staleness {
+    Merged,
+    UpstreamGone,
+    /// Days since the last commit
+    Inactive(u64),
+}
+
+impl Worktree {
+    pub fn short_head(&self) -> &str {
+        &self.head[..self.head.len().min(7)]
+    }
+
+    /// Branch name without the `refs/heads/` prefix
+    pub fn branch_name(&self) -> Option<&str> {
+        self.branch
+            .as_deref()
+            .map(|b| b.strip_prefix("refs/heads/").unwrap_or(b))
+    }
+
+    /// Branch name, or a placeholder for detached and bare worktrees
+    pub fn label(&self) -> &str {
+        match self.branch_name() {
+            Some(b) => b,
+            None if self.bare => "(bare)",
+            None => "(detached HEAD)",
+        }
+    }
+
+    /// Working tree and branch status, computed on the first call and cached.
+    /// `main_ref` is only used by that first call. None for bare and
+    /// prunable worktrees or if git fails.
+    pub fn status(&self, main_ref: Option<&str>) -> Option<&WorktreeStatus> {
+        self.status
+            .get_or_init(|| {
+                if self.bare || self.prunable.is_some() {
+                    None
+                } else {
+                    read_status(&self.path, main_ref)
+                }
+            })
+            .as_ref()
+    }
+}
+
+/// Computes the status of every worktree in parallel so that later
+/// `status()` calls return immediately
+pub fn load_statuses(worktrees: &[Worktree], main_ref: Option<&str>) {
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get() * 2)
+        .unwrap_or(8)
+        .min(worktrees.len());
+    let next = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match worktrees.get(i) {
+                    Some(wt) => {
+                        wt.status(main_ref);
+                    }
+                    None => break,
+                }
+            });
+        }
+    });
+}
+
+/// Counts from `git status --porcelain=v2 --branch`:
+/// (modified, untracked, upstream, upstream ahead/behind)
+fn parse_status_v2(output: &str) -> (usize, usize, Option<String>, Option<(usize, usize)>) {
+    let mut modified = 0;
+    let mut untracked = 0;
+    let mut upstream = None;
+    let mut ahead_behind = None;
+
+    for line in output.lines() {
+        if let Some(up) = line.strip_prefix("# branch.upstream ") {
+            upstream = Some(up.to_string());
+        } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            let mut parts = ab.split(' ');
+            let ahead = parts.next().and_then(|a| a.strip_prefix('+')?.parse().ok());
+            let behind = parts.next().and_then(|b| b.strip_prefix('-')?.parse().ok());
+            if let (Some(a), Some(b)) = (ahead, behind) {
+                ahead_behind = Some((a, b));
+            }
+        } else if line.starts_with("? ") {
+            untracked += 1;
+        } else if line.starts_with("1 ") || line.starts_with("2 ") || line.starts_with("u ") {
+            modified += 1;
+        }
+    }
+
+    (modified, untracked, upstream, ahead_behind)
+}
+
+fn git_output(path: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(["-C", path])
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_status(path: &str, main_ref: Option<&str>) -> Option<WorktreeStatus> {
+    let status = git_output(path, &["status", "--porcelain=v2", "--branch"])?;
+    let (modified, untracked, upstream, upstream_ahead_behind) = parse_status_v2(&status);
+
+    let (last_commit_time, last_commit_subject) =
+        match git_output(path, &["log", "-1", "--format=%ct%x09%s"]) {
+            Some(log) => {
+                let (time, subject) = log.trim_end().split_once('\t').unwrap_or(("0", ""));
+                (time.parse().unwrap_or(0), subject.to_string())
+            }
+            None => (0, String::new()),
+        };
+
+    let main_ahead_behind = main_ref.and_then(|main| {
+        let range = format!("HEAD...{}", main);
+        let counts = git_output(path, &["rev-list", "--left-right", "--count", &range])?;
+        let (ahead, behind) = counts.trim().split_once('\t')?;
+        Some((ahead.parse().ok()?, behind.parse().ok()?))
+    });
+
+    Some(WorktreeStatus {
+        modified,
+        untracked,
+        upstream,
+        upstream_ahead_behind,
+        main_ahead_behind,
+        last_commit_subject,
+        last_commit_time,
+    })
+}
+
+/// Work that removing a worktree (and its branch) could throw away
+#[derive(Debug, Default)]
+pub struct RemovalReport {
+    /// `git status --short` lines for modified and untracked files
+    pub changed_files: Vec<String>,
+    /// One-line commits not reachable from any remote-tracking branch
+    pub unpushed: Vec<String>,
+    /// One-line commits not in the main branch
+    pub unmerged: Vec<String>,
+    /// `git stash list` lines for stashes made on the branch
+    pub stashes: Vec<String>,
+    /// False if the repository has no remotes, so `unpushed` was not checked
+    pub has_remotes: bool,
+}
+
+impl RemovalReport {
+    /// True if a forced removal would discard uncommitted files
+    pub fn loses_files(&self) -> bool {
+        !self.changed_files.is_empty()
+    }
+
+    /// True if force-deleting the branch would drop commits found nowhere else
+    pub fn loses_commits(&self) -> bool {
+        if self.has_remotes {
+            !self.unpushed.is_empty()
+        } else {
+            !self.unmerged.is_empty()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed_files.is_empty()
+            && self.unpushed.is_empty()
+            && self.unmerged.is_empty()
+            && self.stashes.is_empty()
+    }
+}
+
+/// Whether a `git stash list` line was made on `branch`. Stash subjects look
+/// like `stash@{0}: WIP on feat/x: 9f45081 msg` or `stash@{1}: On feat/x: msg`.
+fn stash_is_on_branch(line: &str, branch: &str) -> bool {
+    let subject = match line.split_once(": ") {
+        Some((_, subject)) => subject,
+        None => return false,
+    };
+    let rest = subject
+        .strip_prefix("WIP on ")
+        .or_else(|| subject.strip_prefix("On "));
+    match rest.and_then(|r| r.strip_prefix(branch)) {
+        Some(after) => after.starts_with(':'),
+        None => false,
+    }
+}
+
+fn output_lines(output: Option<String>) -> Vec<String> {
+    output
+        .map(|o| o.lines().map(|l| l.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// `git status --short` lines for the worktree at `path`
+pub fn changed_files(path: &str) -> Vec<String> {
+    output_lines(git_output(path, &["status", "--short"]))
+}
+
+/// The last `count` commits at `path` as `<hash> <subject> (<age>)`
+pub fn recent_commits(path: &str, count: usize) -> Vec<String> {
+    let count = format!("-{}", count);
+    output_lines(git_output(path, &["log", &count, "--format=%h %s (%cr)"]))
+}
+
+/// Untracked and ignored paths at `path`, relative to it. Directories with
+/// nothing tracked inside are listed once, ending in `/`.
+pub fn untracked_paths(path: &str) -> Vec<String> {
+    git_output(path, &["ls-files", "-z", "--others", "--directory"])
+        .map(|o| {
+            o.split('\0')
+                .filter(|p| !p.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Collects everything in `wt` that would be lost or left behind by removing it
+pub fn removal_report(wt: &Worktree, main_ref: Option<&str>) -> RemovalReport {
+    let path = wt.path.as_str();
+    let changed_files = changed_files(path);
+
+    // Without any remote, "not on a remote" would be the whole history
+    let has_remotes = !remotes().is_empty();
+    let unpushed = if !has_remotes {
+        Vec::new()
+    } else {
+        output_lines(git_output(
+            path,
+            &["log", "--oneline", "HEAD", "--not", "--remotes"],
+        ))
+    };
+
+    let unmerged = match main_ref {
+        Some(main) => output_lines(git_output(
+            path,
+            &["log", "--oneline", &format!("{}..HEAD", main)],
+        )),
+        None => Vec::new(),
+    };
+
+    let stashes = match wt.branch_name() {
+        Some(branch) => output_lines(git_output(path, &["stash", "list"]))
+            .into_iter()
+            .filter(|line| stash_is_on_branch(line, branch))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    RemovalReport {
+        changed_files,
+        unpushed,
+        unmerged,
+        stashes,
+        has_remotes,
+    }
+}
+
+/// The branch other branches merge into: `origin/HEAD` if known, otherwise
+/// a local `main` or `master`
+pub fn main_branch_ref() -> Option<String> {
+    let output = Command::new("git")
+        .args([
+            "symbolic-ref",
+            "--quiet",
+            "--short",
+            "refs/remotes/origin/HEAD",
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let remote_head = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !remote_head.is_empty() {
+        return Some(remote_head);
+    }
+
+    ["main", "master"]
+        .iter()
+        .find(|b| branch_exists(b))
+        .map(|b| b.to_string())
 }
 
 pub fn is_git_repo() -> bool {
@@ -16,56 +373,218 @@ pub fn is_git_repo() -> bool {
         .unwrap_or(false)
 }
 
-fn parse_worktree_line(line: &str) -> Option<Worktree> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() >= 3 {
-        Some(Worktree {
-            path: parts[0].to_string(),
-            hash: parts[1].to_string(),
-            branch: parts[2].trim_matches(|c| c == '[' || c == ']').to_string(),
-        })
-    } else {
-        None
+/// Parses `git worktree list --porcelain -z` output. Attributes are
+/// NUL-terminated and each record ends with an empty attribute.
+fn parse_porcelain(output: &str) -> Vec<Worktree> {
+    let mut worktrees = Vec::new();
+    let mut current: Option<Worktree> = None;
+
+    for field in output.split('\0') {
+        if field.is_empty() {
+            worktrees.extend(current.take());
+            continue;
+        }
+
+        let (key, value) = match field.split_once(' ') {
+            Some((k, v)) => (k, Some(v)),
+            None => (field, None),
+        };
+
+        if key == "worktree" {
+            worktrees.extend(current.take());
+            current = Some(Worktree {
+                path: value.unwrap_or_default().to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let wt = match current.as_mut() {
+            Some(wt) => wt,
+            None => continue,
+        };
+
+        match key {
+            "HEAD" => wt.head = value.unwrap_or_default().to_string(),
+            "branch" => wt.branch = value.map(|v| v.to_string()),
+            "detached" => wt.detached = true,
+            "bare" => wt.bare = true,
+            "locked" => wt.locked = Some(value.unwrap_or_default().to_string()),
+            "prunable" => wt.prunable = Some(value.unwrap_or_default().to_string()),
+            _ => {}
+        }
     }
+
+    worktrees.extend(current);
+    worktrees
 }
 
 pub fn worktree_list() -> Vec<Worktree> {
-    let output = match Command::new("git").args(["worktree", "list"]).output() {
+    let output = match Command::new("git")
+        .args(["worktree", "list", "--porcelain", "-z"])
+        .output()
+    {
         Ok(o) if o.status.success() => o,
         _ => return vec![],
     };
 
-    String::from_utf8_lossy(&output.stdout)
-        .lines()
-        .filter_map(parse_worktree_line)
-        .collect()
+    parse_porcelain(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Index of the worktree containing `dir`. The deepest match wins so a
+/// worktree nested inside another is not mistaken for its parent.
+fn containing_worktree(worktrees: &[Worktree], dir: &Path) -> Option<usize> {
+    worktrees
+        .iter()
+        .enumerate()
+        .filter(|(_, wt)| !wt.path.is_empty() && dir.starts_with(&wt.path))
+        .max_by_key(|(_, wt)| wt.path.len())
+        .map(|(i, _)| i)
+}
+
+/// Index of the worktree the current directory is in
+pub fn current_worktree(worktrees: &[Worktree]) -> Option<usize> {
+    let dir = std::env::current_dir().ok()?.canonicalize().ok()?;
+    containing_worktree(worktrees, &dir)
+}
+
+/// Worktrees whose branch or directory name matches `query`, best first.
+/// An exact name match is returned alone.
+pub fn match_worktrees(query: &str, worktrees: &[Worktree]) -> Vec<usize> {
+    let dir_name = |wt: &Worktree| {
+        Path::new(&wt.path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.to_string())
+            .unwrap_or_default()
+    };
+
+    if let Some(i) = worktrees
+        .iter()
+        .position(|wt| wt.branch_name() == Some(query) || dir_name(wt) == query)
+    {
+        return vec![i];
+    }
+
+    let mut scored: Vec<(usize, i32)> = worktrees
+        .iter()
+        .enumerate()
+        .filter_map(|(i, wt)| {
+            let branch = fuzzy::score(query, wt.branch_name().unwrap_or(""));
+            let dir = fuzzy::score(query, &dir_name(wt));
+            branch.max(dir).map(|score| (i, score))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+/// The repository's shared git directory, the same from every worktree
+pub fn common_dir() -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-common-dir"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    if dir.is_absolute() {
+        Some(dir)
+    } else {
+        Some(std::env::current_dir().ok()?.join(dir))
+    }
 }
 
 pub fn main_worktree() -> Option<String> {
     worktree_list().first().map(|wt| wt.path.clone())
 }
 
-fn compute_worktrees_dir(main_path: &str) -> Option<String> {
-    let path = std::path::Path::new(main_path);
-    let repo_name = path.file_name()?.to_str()?;
-    let parent = path.parent()?.to_str()?;
-    Some(format!("{}/{}-worktrees", parent, repo_name))
+/// Placeholders a worktree path template may use
+pub const PATH_PLACEHOLDERS: &[&str] = &["repo", "branch", "branch_slug", "user", "date"];
+
+/// Values for a worktree path template, besides the repository name
+pub struct PathVars<'a> {
+    pub branch: &'a str,
+    pub user: &'a str,
+    /// `YYYY-MM-DD`
+    pub date: &'a str,
 }
 
-pub fn worktrees_dir() -> Option<String> {
-    let main = main_worktree()?;
-    compute_worktrees_dir(&main)
+/// Removes `.` and `..` components without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
 }
 
-/// Returns Ok(true) if created with new branch, Ok(false) if existing branch, Err on failure
-pub fn worktree_add(path: &str, branch: &str) -> Result<bool, ()> {
+/// Where the worktree for a branch goes: `template` rendered with `vars`, a
+/// leading `~/` expanded to `home` and relative paths resolved against the
+/// main worktree. None if the template is invalid or the main worktree has
+/// no name.
+pub fn compute_worktree_path(
+    template: &str,
+    main_path: &str,
+    vars: &PathVars,
+    home: &Path,
+) -> Option<String> {
+    let main = Path::new(main_path);
+    let repo = main.file_name()?.to_str()?;
+    let slug = vars.branch.replace('/', "-");
+
+    let rendered = template::render(template, |name| {
+        let value = match name {
+            "repo" => repo,
+            "branch" => vars.branch,
+            "branch_slug" => &slug,
+            "user" => vars.user,
+            "date" => vars.date,
+            _ => return None,
+        };
+        Some(value.to_string())
+    })
+    .ok()?;
+
+    let path = match rendered.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None => main.join(rendered),
+    };
+    normalize(&path).to_str().map(|s| s.to_string())
+}
+
+/// A registered worktree that `path` would clash with: the same directory,
+/// one inside it, or one it would sit inside. Only the main worktree may
+/// contain others, for layouts like `.worktrees/<branch>`.
+pub fn overlapping_worktree<'a>(path: &str, worktrees: &'a [Worktree]) -> Option<&'a Worktree> {
+    let path = Path::new(path);
+    worktrees.iter().enumerate().find_map(|(i, wt)| {
+        let other = Path::new(&wt.path);
+        let inside = path.starts_with(other) && (i > 0 || path == other);
+        let clash = inside || other.starts_with(path);
+        clash.then_some(wt)
+    })
+}
+
+/// Returns Ok(true) if created with new branch, Ok(false) if existing branch, Err on failure.
+/// `base` is the start point for a new branch; it is ignored for existing branches.
+pub fn worktree_add(path: &str, branch: &str, base: Option<&str>) -> Result<bool, ()> {
     // Try with -b (new branch)
-    if let Ok(s) = Command::new("git")
-        .args(["worktree", "add", "-b", branch, path])
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-    {
+    let mut cmd = Command::new("git");
+    cmd.args(["worktree", "add", "-b", branch, path]);
+    if let Some(base) = base {
+        // A feature branch should not track the branch it started from
+        cmd.args(["--no-track", base]);
+    }
+    if let Ok(s) = cmd.stdout(Stdio::null()).stderr(Stdio::null()).status() {
         if s.success() {
             return Ok(true);
         }
@@ -106,6 +625,17 @@ pub fn worktree_force_remove(path: &str) -> bool {
         .unwrap_or(false)
 }
 
+/// Drops administrative entries for worktrees whose directories are gone
+pub fn worktree_prune() -> bool {
+    Command::new("git")
+        .args(["worktree", "prune"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
 pub fn branch_delete(branch: &str) -> bool {
     Command::new("git")
         .args(["branch", "-d", branch])
@@ -126,21 +656,294 @@ pub fn branch_force_delete(branch: &str) -> bool {
         .unwrap_or(false)
 }
 
-pub fn repo_name() -> Option<String> {
+/// Root of the current worktree
+pub fn toplevel() -> Option<String> {
     let output = Command::new("git")
         .args(["rev-parse", "--show-toplevel"])
+        .stderr(Stdio::null())
         .output()
         .ok()?;
     if !output.status.success() {
         return None;
     }
-    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub fn repo_name() -> Option<String> {
+    let path = toplevel()?;
     std::path::Path::new(&path)
         .file_name()
         .and_then(|n| n.to_str())
         .map(|s| s.to_string())
 }
 
+pub fn branch_exists(branch: &str) -> bool {
+    Command::new("git")
+        .args([
+            "show-ref",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{}", branch),
+        ])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Short names of the refs under `prefixes`, skipping symbolic refs such as
+/// `origin/HEAD`
+fn list_refs(prefixes: &[&str]) -> Vec<String> {
+    let output = match Command::new("git")
+        .args(["for-each-ref", "--format=%(refname:short)\t%(symref)"])
+        .args(prefixes)
+        .output()
+    {
+        Ok(o) if o.status.success() => o,
+        _ => return vec![],
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| match line.split_once('\t') {
+            Some((name, "")) => Some(name.to_string()),
+            Some(_) => None,
+            None => Some(line.to_string()),
+        })
+        .collect()
+}
+
+/// Local branches, remote-tracking branches and tags, in that order
+pub fn base_refs() -> Vec<String> {
+    list_refs(&["refs/heads", "refs/remotes", "refs/tags"])
+}
+
+/// Local and remote branch names without the remote, most recently
+/// committed first and without duplicates
+pub fn recent_branch_names(limit: usize) -> Vec<String> {
+    let output = match Command::new("git")
+        .args([
+            "for-each-ref",
+            "--sort=-committerdate",
+            "--format=%(refname)\t%(symref)",
+            "refs/heads",
+            "refs/remotes",
+        ])
+        .output()
+    {
+        Ok(o) if o.status.success() => o,
+        _ => return vec![],
+    };
+
+    let remotes = remotes();
+    let mut names: Vec<String> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let (refname, symref) = line.split_once('\t').unwrap_or((line, ""));
+        if !symref.is_empty() {
+            continue;
+        }
+        let name = match refname.strip_prefix("refs/heads/") {
+            Some(name) => name.to_string(),
+            None => match refname
+                .strip_prefix("refs/remotes/")
+                .and_then(|r| split_remote_ref_with(&remotes, r))
+            {
+                Some((_, name)) => name,
+                None => continue,
+            },
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+        if names.len() == limit {
+            break;
+        }
+    }
+    names
+}
+
+pub fn local_branches() -> Vec<String> {
+    list_refs(&["refs/heads"])
+}
+
+/// Whether git accepts `name` as a branch name
+pub fn check_branch_name(name: &str) -> bool {
+    Command::new("git")
+        .args(["check-ref-format", "--branch", name])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Remote-tracking branches such as `origin/feat/x`
+pub fn remote_branches() -> Vec<String> {
+    list_refs(&["refs/remotes"])
+}
+
+/// `remote_branches` on `remote` only
+pub fn remote_branches_on(remote: &str) -> Vec<String> {
+    list_refs(&[&format!("refs/remotes/{}", remote)])
+}
+
+pub fn remotes() -> Vec<String> {
+    Command::new("git")
+        .arg("remote")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Splits `origin/feat/x` into ("origin", "feat/x") if it names a branch on
+/// one of `remotes`. The longest matching remote name wins.
+fn split_remote_ref_with(remotes: &[String], refname: &str) -> Option<(String, String)> {
+    remotes
+        .iter()
+        .filter_map(|r| {
+            let branch = refname.strip_prefix(r.as_str())?.strip_prefix('/')?;
+            (!branch.is_empty()).then(|| (r.clone(), branch.to_string()))
+        })
+        .max_by_key(|(r, _)| r.len())
+}
+
+pub fn split_remote_ref(refname: &str) -> Option<(String, String)> {
+    split_remote_ref_with(&remotes(), refname)
+}
+
+pub fn fetch(remote: &str, branch: &str) -> bool {
+    Command::new("git")
+        .args(["fetch", "--quiet", remote, branch])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// `origin` if it exists, otherwise the first configured remote
+pub fn default_remote() -> Option<String> {
+    let remotes = remotes();
+    if remotes.iter().any(|r| r == "origin") {
+        return Some("origin".to_string());
+    }
+    remotes.into_iter().next()
+}
+
+/// Pull request number from `123`, `#123` or `pr/123`
+pub fn pull_request_number(s: &str) -> Option<u32> {
+    let digits = s
+        .strip_prefix('#')
+        .or_else(|| s.strip_prefix("pr/"))
+        .unwrap_or(s);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|&n| n > 0)
+}
+
+pub fn fetch_all() -> bool {
+    Command::new("git")
+        .args(["fetch", "--all", "--prune", "--quiet"])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Fetches `refs/pull/<number>/head` from `remote` into local branch `branch`
+/// and records it as the branch's upstream so `git pull` keeps working. An
+/// existing branch is only fast-forwarded, so commits made on it are never
+/// lost.
+pub fn fetch_pull_request(remote: &str, number: u32, branch: &str) -> bool {
+    let pull_ref = format!("refs/pull/{}/head", number);
+    let fetched = Command::new("git")
+        .args([
+            "fetch",
+            "--quiet",
+            remote,
+            &format!("{}:refs/heads/{}", pull_ref, branch),
+        ])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+
+    fetched
+        && config_set_local(&format!("branch.{}.remote", branch), Some(remote))
+        && config_set_local(&format!("branch.{}.merge", branch), Some(&pull_ref))
+}
+
+/// Adds a worktree on new branch `branch` tracking `remote_ref` (e.g. `origin/feat/x`)
+pub fn worktree_add_tracking(path: &str, branch: &str, remote_ref: &str) -> bool {
+    Command::new("git")
+        .args(["worktree", "add", "--track", "-b", branch, path, remote_ref])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// What local `branch` tracks, such as `origin/feat/x`
+pub fn upstream(branch: &str) -> Option<String> {
+    git_output(
+        ".",
+        &[
+            "rev-parse",
+            "--abbrev-ref",
+            &format!("{}@{{upstream}}", branch),
+        ],
+    )
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+}
+
+pub fn set_upstream(branch: &str, remote_ref: &str) -> bool {
+    Command::new("git")
+        .args(["branch", "--quiet", "--set-upstream-to", remote_ref, branch])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// All `name value` pairs whose name matches `pattern`, in config order
+pub fn config_get_regexp(pattern: &str) -> Vec<(String, String)> {
+    let output = match Command::new("git")
+        .args(["config", "--get-regexp", pattern])
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(o) if o.status.success() => o,
+        _ => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| match line.split_once(' ') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => (line.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Sets `key` in the repository's local git config, or unsets it if `value` is None
+pub fn config_set_local(key: &str, value: Option<&str>) -> bool {
+    let mut cmd = Command::new("git");
+    match value {
+        Some(v) => cmd.args(["config", "--local", key, v]),
+        None => cmd.args(["config", "--local", "--unset", key]),
+    };
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
 pub fn current_branch() -> Option<String> {
     let output = Command::new("git")
         .args(["branch", "--show-current"])
@@ -161,52 +964,408 @@ pub fn current_branch() -> Option<String> {
 mod tests {
     use super::*;
 
+    const SHA: &str = "9f45081f150a6b488c2693df1c8abb7e2f109a39";
+
     #[test]
-    fn test_parse_worktree_line_valid() {
-        let line = "/home/user/repo  abc1234 [main]";
-        let wt = parse_worktree_line(line).unwrap();
-        assert_eq!(wt.path, "/home/user/repo");
-        assert_eq!(wt.hash, "abc1234");
-        assert_eq!(wt.branch, "main");
+    fn test_parse_porcelain_valid() {
+        let out = format!("worktree /home/user/repo\0HEAD {SHA}\0branch refs/heads/main\0\0");
+        let wts = parse_porcelain(&out);
+        assert_eq!(wts.len(), 1);
+        assert_eq!(wts[0].path, "/home/user/repo");
+        assert_eq!(wts[0].head, SHA);
+        assert_eq!(wts[0].short_head(), "9f45081");
+        assert_eq!(wts[0].branch.as_deref(), Some("refs/heads/main"));
+        assert_eq!(wts[0].branch_name(), Some("main"));
+        assert!(!wts[0].detached && !wts[0].bare);
+        assert!(wts[0].locked.is_none() && wts[0].prunable.is_none());
     }
 
     #[test]
-    fn test_parse_worktree_line_strips_brackets() {
-        let line = "/tmp/wt  deadbeef [feat/login]";
-        let wt = parse_worktree_line(line).unwrap();
-        assert_eq!(wt.branch, "feat/login");
+    fn test_parse_porcelain_strips_refs_heads() {
+        let out = format!("worktree /tmp/wt\0HEAD {SHA}\0branch refs/heads/feat/login\0\0");
+        let wt = &parse_porcelain(&out)[0];
+        assert_eq!(wt.branch_name(), Some("feat/login"));
+        assert_eq!(wt.label(), "feat/login");
     }
 
     #[test]
-    fn test_parse_worktree_line_too_few_parts() {
-        assert!(parse_worktree_line("/only/path").is_none());
-        assert!(parse_worktree_line("").is_none());
+    fn test_parse_porcelain_empty_or_garbage() {
+        assert!(parse_porcelain("").is_empty());
+        assert!(parse_porcelain("HEAD abc\0branch refs/heads/x\0\0").is_empty());
     }
 
     #[test]
-    fn test_parse_worktree_line_extra_parts() {
-        let line = "/path  abc1234 [main] extra";
-        let wt = parse_worktree_line(line).unwrap();
+    fn test_parse_porcelain_ignores_unknown_attributes() {
+        let out = format!("worktree /path\0HEAD {SHA}\0branch refs/heads/main\0future thing\0\0");
+        let wt = &parse_porcelain(&out)[0];
         assert_eq!(wt.path, "/path");
-        assert_eq!(wt.hash, "abc1234");
-        assert_eq!(wt.branch, "main");
+        assert_eq!(wt.branch_name(), Some("main"));
+    }
+
+    #[test]
+    fn test_parse_porcelain_path_with_spaces() {
+        let out = format!("worktree /tmp/my repo/wt one\0HEAD {SHA}\0branch refs/heads/x\0\0");
+        let wt = &parse_porcelain(&out)[0];
+        assert_eq!(wt.path, "/tmp/my repo/wt one");
+    }
+
+    #[test]
+    fn test_parse_porcelain_detached() {
+        let out = format!("worktree /tmp/det\0HEAD {SHA}\0detached\0\0");
+        let wt = &parse_porcelain(&out)[0];
+        assert!(wt.detached);
+        assert!(wt.branch.is_none());
+        assert_eq!(wt.label(), "(detached HEAD)");
+    }
+
+    #[test]
+    fn test_parse_porcelain_bare() {
+        let wt = &parse_porcelain("worktree /srv/repo.git\0bare\0\0")[0];
+        assert!(wt.bare);
+        assert!(wt.head.is_empty());
+        assert_eq!(wt.short_head(), "");
+        assert_eq!(wt.label(), "(bare)");
+    }
+
+    #[test]
+    fn test_parse_porcelain_locked() {
+        let out = format!(
+            "worktree /a\0HEAD {SHA}\0detached\0locked on usb\0\0\
+             worktree /b\0HEAD {SHA}\0branch refs/heads/b\0locked\0\0"
+        );
+        let wts = parse_porcelain(&out);
+        assert_eq!(wts[0].locked.as_deref(), Some("on usb"));
+        assert_eq!(wts[1].locked.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_parse_porcelain_prunable() {
+        let out = format!(
+            "worktree /gone\0HEAD {SHA}\0branch refs/heads/gone\0\
+             prunable gitdir file points to non-existent location\0\0"
+        );
+        let wt = &parse_porcelain(&out)[0];
+        assert_eq!(
+            wt.prunable.as_deref(),
+            Some("gitdir file points to non-existent location")
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_multiple() {
+        let out = format!(
+            "worktree /repo\0HEAD {SHA}\0branch refs/heads/main\0\0\
+             worktree /repo-worktrees/feat-x\0HEAD {SHA}\0branch refs/heads/feat/x\0\0\
+             worktree /repo-worktrees/det\0HEAD {SHA}\0detached\0\0"
+        );
+        let wts = parse_porcelain(&out);
+        assert_eq!(wts.len(), 3);
+        assert_eq!(wts[1].path, "/repo-worktrees/feat-x");
+        assert!(wts[2].detached);
     }
 
     #[test]
-    fn test_compute_worktrees_dir_normal() {
-        let result = compute_worktrees_dir("/home/user/my-repo").unwrap();
-        assert_eq!(result, "/home/user/my-repo-worktrees");
+    fn test_parse_porcelain_without_trailing_terminator() {
+        let out = format!("worktree /repo\0HEAD {SHA}\0branch refs/heads/main");
+        let wts = parse_porcelain(&out);
+        assert_eq!(wts.len(), 1);
+        assert_eq!(wts[0].branch_name(), Some("main"));
     }
 
     #[test]
-    fn test_compute_worktrees_dir_nested() {
-        let result = compute_worktrees_dir("/a/b/c/project").unwrap();
-        assert_eq!(result, "/a/b/c/project-worktrees");
+    fn test_parse_status_v2_counts() {
+        let out = "# branch.oid 9f45081\n\
+                   # branch.head feat/x\n\
+                   # branch.upstream origin/feat/x\n\
+                   # branch.ab +2 -5\n\
+                   1 .M N... 100644 100644 100644 a b src/main.rs\n\
+                   2 R. N... 100644 100644 100644 a b R100 new.rs\told.rs\n\
+                   u UU N... 100644 100644 100644 100644 a b c conflict.rs\n\
+                   ? notes.txt\n\
+                   ? my file.txt\n\
+                   ! target/\n";
+        let (modified, untracked, upstream, ab) = parse_status_v2(out);
+        assert_eq!(modified, 3);
+        assert_eq!(untracked, 2);
+        assert_eq!(upstream.as_deref(), Some("origin/feat/x"));
+        assert_eq!(ab, Some((2, 5)));
     }
 
     #[test]
-    fn test_compute_worktrees_dir_root_path() {
+    fn test_parse_status_v2_upstream_gone() {
+        let out = "# branch.oid 9f45081\n# branch.head x\n# branch.upstream origin/x\n";
+        let (modified, untracked, upstream, ab) = parse_status_v2(out);
+        assert_eq!((modified, untracked), (0, 0));
+        let status = WorktreeStatus {
+            upstream,
+            upstream_ahead_behind: ab,
+            ..Default::default()
+        };
+        assert!(status.upstream_gone());
+        assert!(!status.is_dirty());
+    }
+
+    #[test]
+    fn test_status_merged() {
+        let mut status = WorktreeStatus {
+            main_ahead_behind: Some((0, 3)),
+            ..Default::default()
+        };
+        assert!(status.merged());
+        status.main_ahead_behind = Some((1, 0));
+        assert!(!status.merged());
+        status.main_ahead_behind = Some((0, 0));
+        assert!(!status.merged());
+        status.main_ahead_behind = None;
+        assert!(!status.merged());
+    }
+
+    #[test]
+    fn test_status_skipped_for_bare_and_prunable() {
+        let bare = Worktree {
+            path: "/nonexistent".to_string(),
+            bare: true,
+            ..Default::default()
+        };
+        assert!(bare.status(None).is_none());
+        let prunable = Worktree {
+            path: "/nonexistent".to_string(),
+            prunable: Some(String::new()),
+            ..Default::default()
+        };
+        assert!(prunable.status(None).is_none());
+    }
+
+    #[test]
+    fn test_containing_worktree_prefers_deepest() {
+        let wts = vec![
+            Worktree {
+                path: "/repo".to_string(),
+                ..Default::default()
+            },
+            Worktree {
+                path: "/repo/.worktrees/feat".to_string(),
+                ..Default::default()
+            },
+            Worktree {
+                path: "/repo-worktrees/fix".to_string(),
+                ..Default::default()
+            },
+        ];
+        let find = |d: &str| containing_worktree(&wts, Path::new(d));
+        assert_eq!(find("/repo/src"), Some(0));
+        assert_eq!(find("/repo/.worktrees/feat/src"), Some(1));
+        assert_eq!(find("/repo-worktrees/fix"), Some(2));
+        assert_eq!(find("/elsewhere"), None);
+    }
+
+    #[test]
+    fn test_split_remote_ref() {
+        let remotes = vec!["origin".to_string(), "upstream".to_string()];
+        assert_eq!(
+            split_remote_ref_with(&remotes, "origin/feat/x"),
+            Some(("origin".to_string(), "feat/x".to_string()))
+        );
+        assert_eq!(split_remote_ref_with(&remotes, "main"), None);
+        assert_eq!(split_remote_ref_with(&remotes, "origin"), None);
+        assert_eq!(split_remote_ref_with(&remotes, "originals/x"), None);
+    }
+
+    #[test]
+    fn test_split_remote_ref_longest_remote_wins() {
+        let remotes = vec!["team".to_string(), "team/mirror".to_string()];
+        assert_eq!(
+            split_remote_ref_with(&remotes, "team/mirror/main"),
+            Some(("team/mirror".to_string(), "main".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_pull_request_number() {
+        assert_eq!(pull_request_number("123"), Some(123));
+        assert_eq!(pull_request_number("#42"), Some(42));
+        assert_eq!(pull_request_number("pr/7"), Some(7));
+        assert_eq!(pull_request_number("feat/123"), None);
+        assert_eq!(pull_request_number("12a"), None);
+        assert_eq!(pull_request_number("#"), None);
+        assert_eq!(pull_request_number("0"), None);
+        assert_eq!(pull_request_number("+5"), None);
+    }
+
+    const DEFAULT_TEMPLATE: &str = "../{repo}-worktrees/{branch_slug}";
+
+    fn path(template: &str, main: &str, branch: &str) -> Option<String> {
+        let vars = PathVars {
+            branch,
+            user: "kaye",
+            date: "2026-10-18",
+        };
+        compute_worktree_path(template, main, &vars, Path::new("/home/kaye"))
+    }
+
+    #[test]
+    fn test_compute_worktree_path_default() {
+        assert_eq!(
+            path(DEFAULT_TEMPLATE, "/home/user/my-repo", "feat/login").unwrap(),
+            "/home/user/my-repo-worktrees/feat-login"
+        );
+        assert_eq!(
+            path(DEFAULT_TEMPLATE, "/a/b/c/project", "fix").unwrap(),
+            "/a/b/c/project-worktrees/fix"
+        );
+    }
+
+    #[test]
+    fn test_compute_worktree_path_root_path() {
         // "/" has no file_name
-        assert!(compute_worktrees_dir("/").is_none());
+        assert!(path(DEFAULT_TEMPLATE, "/", "x").is_none());
+    }
+
+    #[test]
+    fn test_compute_worktree_path_templates() {
+        assert_eq!(
+            path(".worktrees/{branch}", "/src/shuu", "feat/x").unwrap(),
+            "/src/shuu/.worktrees/feat/x"
+        );
+        assert_eq!(
+            path(
+                "~/worktrees/{repo}/{user}-{date}-{branch_slug}",
+                "/src/shuu",
+                "feat/x"
+            )
+            .unwrap(),
+            "/home/kaye/worktrees/shuu/kaye-2026-10-18-feat-x"
+        );
+        assert_eq!(
+            path("/wt/./{repo}/{branch}", "/src/shuu", "x").unwrap(),
+            "/wt/shuu/x"
+        );
+        assert!(path("{nope}/{branch}", "/src/shuu", "x").is_none());
+    }
+
+    #[test]
+    fn test_overlapping_worktree() {
+        let wt = |path: &str| Worktree {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let worktrees = vec![wt("/src/shuu"), wt("/wt/feat")];
+
+        assert!(overlapping_worktree("/src/shuu/.worktrees/x", &worktrees).is_none());
+        assert!(overlapping_worktree("/wt/feat-x", &worktrees).is_none());
+        assert_eq!(
+            overlapping_worktree("/wt/feat", &worktrees).unwrap().path,
+            "/wt/feat"
+        );
+        assert_eq!(
+            overlapping_worktree("/wt/feat/x", &worktrees).unwrap().path,
+            "/wt/feat"
+        );
+        assert_eq!(
+            overlapping_worktree("/src", &worktrees).unwrap().path,
+            "/src/shuu"
+        );
+        assert_eq!(
+            overlapping_worktree("/src/shuu", &worktrees).unwrap().path,
+            "/src/shuu"
+        );
+    }
+
+    #[test]
+    fn test_stash_is_on_branch() {
+        assert!(stash_is_on_branch(
+            "stash@{0}: WIP on feat/x: 9f45081 add login",
+            "feat/x"
+        ));
+        assert!(stash_is_on_branch(
+            "stash@{1}: On feat/x: half done",
+            "feat/x"
+        ));
+        assert!(!stash_is_on_branch(
+            "stash@{2}: WIP on feat/x2: 9f45081 add login",
+            "feat/x"
+        ));
+        assert!(!stash_is_on_branch(
+            "stash@{3}: On main: feat/x: note",
+            "feat/x"
+        ));
+        assert!(!stash_is_on_branch("garbage", "feat/x"));
+    }
+
+    #[test]
+    fn test_removal_report_empty() {
+        let report = RemovalReport::default();
+        assert!(report.is_empty());
+        assert!(!report.loses_files());
+        assert!(!report.loses_commits());
+    }
+
+    #[test]
+    fn test_removal_report_loses_commits_without_remotes() {
+        let report = RemovalReport {
+            unmerged: vec!["9f45081 add login".to_string()],
+            ..Default::default()
+        };
+        assert!(report.loses_commits());
+
+        let pushed = RemovalReport {
+            has_remotes: true,
+            ..report
+        };
+        assert!(!pushed.loses_commits());
+    }
+
+    #[test]
+    fn test_staleness() {
+        let now = 100 * 86400;
+        let status = WorktreeStatus {
+            main_ahead_behind: Some((0, 3)),
+            upstream: Some("origin/feat/x".to_string()),
+            last_commit_time: now - 40 * 86400,
+            ..Default::default()
+        };
+        assert_eq!(
+            status.staleness(now, 30),
+            vec![
+                Staleness::Merged,
+                Staleness::UpstreamGone,
+                Staleness::Inactive(40)
+            ]
+        );
+        assert_eq!(
+            status.staleness(now, 60),
+            vec![Staleness::Merged, Staleness::UpstreamGone]
+        );
+    }
+
+    #[test]
+    fn test_staleness_fresh_branch_is_not_merged() {
+        let status = WorktreeStatus {
+            main_ahead_behind: Some((0, 0)),
+            last_commit_time: 1000,
+            ..Default::default()
+        };
+        assert!(status.staleness(1000, 30).is_empty());
+    }
+
+    #[test]
+    fn test_match_worktrees() {
+        let wt = |path: &str, branch: Option<&str>| Worktree {
+            path: path.to_string(),
+            branch: branch.map(|b| format!("refs/heads/{}", b)),
+            ..Default::default()
+        };
+        let worktrees = [
+            wt("/repo", Some("main")),
+            wt("/repo-worktrees/feat-auth", Some("feat/auth")),
+            wt("/repo-worktrees/fix-auth-typo", Some("fix/auth-typo")),
+            wt("/repo-worktrees/scratch", None),
+        ];
+
+        assert_eq!(match_worktrees("feat/auth", &worktrees), vec![1]);
+        assert_eq!(match_worktrees("scratch", &worktrees), vec![3]);
+        assert_eq!(match_worktrees("typo", &worktrees), vec![2]);
+        assert_eq!(match_worktrees("auth", &worktrees), vec![1, 2]);
+        assert!(match_worktrees("zzz", &worktrees).is_empty());
     }
 }
@@ -0,0 +1,108 @@
+/// Whether one path component matches one pattern component
+fn component_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    chars_match(&pattern, &name)
+}
+
+fn chars_match(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            chars_match(&pattern[1..], name)
+                || (!name.is_empty() && chars_match(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => chars_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => chars_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn matches_parts(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            matches_parts(&pattern[1..], path)
+                || (!path.is_empty() && matches_parts(pattern, &path[1..]))
+        }
+        (Some(p), Some(n)) => component_matches(p, n) && matches_parts(&pattern[1..], &path[1..]),
+        _ => false,
+    }
+}
+
+fn split(s: &str) -> Vec<&str> {
+    s.split('/').filter(|c| !c.is_empty()).collect()
+}
+
+/// Whether the relative `path` matches `pattern` as a whole. `*` and `?`
+/// stay within one `/`-separated component, `**` spans any number of them.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    matches_parts(&split(pattern), &split(path))
+}
+
+/// Whether something inside directory `dir` could match `pattern`, so
+/// walking it is worthwhile
+pub fn may_match_inside(pattern: &str, dir: &str) -> bool {
+    let pattern = split(pattern);
+    let dir = split(dir);
+
+    for (i, d) in dir.iter().enumerate() {
+        match pattern.get(i) {
+            Some(&"**") => return true,
+            Some(p) if component_matches(p, d) => {}
+            _ => return false,
+        }
+    }
+    pattern.len() > dir.len()
+}
+
+/// Relative, without `..`, and not empty
+pub fn is_valid(pattern: &str) -> bool {
+    !pattern.trim().is_empty() && !pattern.starts_with('/') && !split(pattern).contains(&"..")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_components() {
+        assert!(matches(".env*", ".env.local"));
+        assert!(matches(".env*", ".env"));
+        assert!(!matches(".env*", "app/.env"));
+        assert!(matches("*.local", ".env.local"));
+        assert!(matches("config/?.json", "config/a.json"));
+        assert!(!matches("config/?.json", "config/ab.json"));
+        assert!(matches("メモ?.txt", "メモ1.txt"));
+        assert!(matches("node_modules", "node_modules/"));
+    }
+
+    #[test]
+    fn test_matches_double_star() {
+        assert!(matches("**/.env", ".env"));
+        assert!(matches("**/.env", "apps/web/.env"));
+        assert!(matches(".idea/**", ".idea/workspace.xml"));
+        assert!(matches("a/**/z", "a/z"));
+        assert!(matches("a/**/z", "a/b/c/z"));
+        assert!(!matches("a/**/z", "b/z"));
+    }
+
+    #[test]
+    fn test_may_match_inside() {
+        assert!(may_match_inside(".idea/workspace.xml", ".idea"));
+        assert!(may_match_inside("apps/*/.env", "apps/web"));
+        assert!(may_match_inside("**/.env", "node_modules"));
+        assert!(!may_match_inside(".env*", "node_modules"));
+        assert!(!may_match_inside(".idea/x", ".vscode"));
+        assert!(!may_match_inside("apps/*/.env", "apps/web/src"));
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid(".env.local"));
+        assert!(is_valid("**/target"));
+        assert!(!is_valid("/etc/passwd"));
+        assert!(!is_valid("../secrets"));
+        assert!(!is_valid(" "));
+    }
+}
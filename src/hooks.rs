@@ -0,0 +1,238 @@
+use crate::config::Origin;
+use crate::{err, info, state, App, BOLD, DIM, NC};
+use std::io::{self, IsTerminal, Write};
+use std::os::fd::AsFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+/// Seconds a hook may run before it is killed, unless `hooks.timeout` says
+/// otherwise
+pub const DEFAULT_TIMEOUT: u64 = 300;
+
+/// Points in a worktree's life where a configured shell command runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    PostCreate,
+    PreRemove,
+    PostSwitch,
+}
+
+impl Hook {
+    /// Setting that holds the command
+    fn key(self) -> &'static str {
+        match self {
+            Hook::PostCreate => "hooks.post_create",
+            Hook::PreRemove => "hooks.pre_remove",
+            Hook::PostSwitch => "hooks.post_switch",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PostCreate => "post-create",
+            Hook::PreRemove => "pre-remove",
+            Hook::PostSwitch => "post-switch",
+        }
+    }
+}
+
+/// The worktree a hook runs for. It is also the hook's working directory.
+pub struct Context<'a> {
+    pub path: &'a str,
+    pub branch: Option<&'a str>,
+    /// Start point of a newly created branch
+    pub base: Option<&'a str>,
+}
+
+impl Context<'_> {
+    fn env(&self, hook: Hook) -> Vec<(&'static str, String)> {
+        vec![
+            ("SHUU_HOOK", hook.name().to_string()),
+            ("SHUU_WORKTREE_PATH", self.path.to_string()),
+            ("SHUU_BRANCH", self.branch.unwrap_or_default().to_string()),
+            ("SHUU_BASE", self.base.unwrap_or_default().to_string()),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// No hook is configured, or the user declined to run it
+    Skipped,
+    Succeeded,
+    Failed,
+}
+
+/// Runs `command` with `sh -c`. Returns None if it was killed for running
+/// past `timeout`. The hook gets its own process group so a timeout also
+/// kills whatever it started, such as `npm install` under a compound command.
+fn execute(
+    command: &str,
+    dir: &str,
+    env: &[(&str, String)],
+    timeout: Duration,
+) -> io::Result<Option<ExitStatus>> {
+    // Hook output goes to stderr with ours, keeping stdout for the worktree path
+    let stdout = io::stderr().as_fd().try_clone_to_owned()?;
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .current_dir(dir)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(stdout))
+        .process_group(0)
+        .spawn()?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            // The group id is the shell's pid
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Commands from `.shuu.toml` come with the repository, so each one needs
+/// the user's approval once before it runs
+fn approved(app: &App, hook: Hook, command: &str) -> bool {
+    let m = app.messages;
+    if state::hook_is_trusted(command) {
+        return true;
+    }
+    if !io::stdin().is_terminal() {
+        err(&m.hook_untrusted.replacen("%s", hook.name(), 1));
+        return false;
+    }
+
+    eprintln!("{}", m.hook_review.replacen("%s", hook.name(), 1));
+    for line in command.lines() {
+        eprintln!("    {DIM}{}{NC}", line);
+    }
+    eprint!("{BOLD}{} [y/N]:{NC} ", m.hook_trust_prompt);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+    if !matches!(answer.trim(), "y" | "Y") {
+        return false;
+    }
+
+    if let Err(e) = state::trust_hook(command) {
+        err(&format!("{}: {}", m.state_write_failed, e));
+    }
+    true
+}
+
+/// Runs the command configured for `hook`, if any, streaming its output
+pub fn run(app: &App, hook: Hook, ctx: &Context) -> Outcome {
+    let m = app.messages;
+    let config = &app.config;
+    let entry = match config.entry(hook.key()) {
+        Some(e) => e,
+        None => return Outcome::Skipped,
+    };
+    if entry.origin == Origin::Repo && !approved(app, hook, &entry.value) {
+        return Outcome::Skipped;
+    }
+    let timeout = config
+        .get("hooks.timeout")
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT);
+
+    let summary = entry.value.lines().next().unwrap_or_default();
+    info(
+        &m.hook_running
+            .replacen("%s", hook.name(), 1)
+            .replacen("%s", summary, 1),
+    );
+
+    let result = execute(
+        &entry.value,
+        ctx.path,
+        &ctx.env(hook),
+        Duration::from_secs(timeout),
+    );
+    let reason = match result {
+        Ok(Some(status)) if status.success() => return Outcome::Succeeded,
+        Ok(Some(status)) => match status.code() {
+            Some(code) => m.hook_exit_code.replacen("%s", &code.to_string(), 1),
+            None => status.to_string(),
+        },
+        Ok(None) => m.hook_timed_out.replacen("%s", &timeout.to_string(), 1),
+        Err(e) => e.to_string(),
+    };
+    err(&m
+        .hook_failed
+        .replacen("%s", hook.name(), 1)
+        .replacen("%s", &reason, 1));
+    Outcome::Failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> Vec<(&'static str, String)> {
+        Context {
+            path: "/tmp",
+            branch: Some("feat/x"),
+            base: None,
+        }
+        .env(Hook::PostCreate)
+    }
+
+    #[test]
+    fn test_context_env() {
+        assert_eq!(
+            env(),
+            vec![
+                ("SHUU_HOOK", "post-create".to_string()),
+                ("SHUU_WORKTREE_PATH", "/tmp".to_string()),
+                ("SHUU_BRANCH", "feat/x".to_string()),
+                ("SHUU_BASE", String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_status_and_env() {
+        let timeout = Duration::from_secs(5);
+        let ok = execute("test \"$SHUU_BRANCH\" = feat/x", "/tmp", &env(), timeout);
+        assert!(ok.unwrap().unwrap().success());
+
+        let failed = execute("exit 3", "/tmp", &env(), timeout);
+        assert_eq!(failed.unwrap().unwrap().code(), Some(3));
+    }
+
+    #[test]
+    fn test_execute_timeout() {
+        let started = Instant::now();
+        let result = execute("sleep 5", "/tmp", &env(), Duration::from_millis(100));
+        assert!(result.unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_execute_timeout_kills_started_processes() {
+        let started = Instant::now();
+        let result = execute("sleep 5; true", "/tmp", &env(), Duration::from_millis(100));
+        assert!(result.unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // A subshell outlives its parent unless the whole group is killed
+        let marker = std::env::temp_dir().join(format!("shuu-hook-marker-{}", std::process::id()));
+        let command = format!("(sleep 1; touch '{}'); true", marker.display());
+        let result = execute(&command, "/tmp", &env(), Duration::from_millis(100));
+        assert!(result.unwrap().is_none());
+        std::thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+    }
+}
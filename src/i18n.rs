@@ -78,6 +78,137 @@ pub struct Messages {
     pub lang_set: &'static str,
     pub settings_reset: &'static str,
     pub settings_reset_done: &'static str,
+    pub help_init_desc: &'static str,
+    pub init_usage: &'static str,
+    pub unsupported_shell: &'static str,
+    pub handoff_failed: &'static str,
+    pub cd_hint: &'static str,
+    pub wt_locked: &'static str,
+    pub wt_prunable: &'static str,
+    pub unknown_option: &'static str,
+    pub missing_value: &'static str,
+    pub unexpected_argument: &'static str,
+    pub see_help: &'static str,
+    pub not_a_terminal: &'static str,
+    pub help_options: &'static str,
+    pub help_opt_branch: &'static str,
+    pub help_opt_base: &'static str,
+    pub help_opt_no_ai: &'static str,
+    pub help_opt_yes: &'static str,
+    pub help_note_exit: &'static str,
+    pub select_base: &'static str,
+    pub base_current_head: &'static str,
+    pub base: &'static str,
+    pub fetching: &'static str,
+    pub fetch_failed: &'static str,
+    pub help_opt_fetch: &'static str,
+    pub settings_base: &'static str,
+    pub base_set: &'static str,
+    pub base_unset: &'static str,
+    pub help_checkout_desc: &'static str,
+    pub help_opt_remote: &'static str,
+    pub fetching_remotes: &'static str,
+    pub fetch_remotes_failed: &'static str,
+    pub select_remote_branch: &'static str,
+    pub no_remote_branches: &'static str,
+    pub no_match: &'static str,
+    pub no_remote: &'static str,
+    pub fetching_pr: &'static str,
+    pub pr_fetch_failed: &'static str,
+    pub tracking: &'static str,
+    pub help_opt_json: &'static str,
+    pub help_opt_porcelain: &'static str,
+    pub help_opt_format: &'static str,
+    pub status_clean: &'static str,
+    pub status_merged: &'static str,
+    pub status_upstream_gone: &'static str,
+    pub report_changed: &'static str,
+    pub report_unpushed: &'static str,
+    pub report_unmerged: &'static str,
+    pub report_stashes: &'static str,
+    pub report_more: &'static str,
+    pub report_clean: &'static str,
+    pub type_to_confirm: &'static str,
+    pub invalid_value: &'static str,
+    pub menu_clean: &'static str,
+    pub help_clean_desc: &'static str,
+    pub help_opt_days: &'static str,
+    pub help_opt_dry_run: &'static str,
+    pub help_opt_delete_branches: &'static str,
+    pub clean_title: &'static str,
+    pub clean_none: &'static str,
+    pub clean_would_remove: &'static str,
+    pub clean_would_prune: &'static str,
+    pub clean_pruned: &'static str,
+    pub clean_not_a_terminal: &'static str,
+    pub clean_delete_branches: &'static str,
+    pub clean_branch_kept: &'static str,
+    pub stale_inactive: &'static str,
+    pub multi_menu_hint: &'static str,
+    pub hint_preview: &'static str,
+    pub no_previous_wt: &'static str,
+    pub previous_wt_gone: &'static str,
+    pub no_wt_matches: &'static str,
+    pub wt_ambiguous: &'static str,
+    pub state_write_failed: &'static str,
+    pub help_switch_query: &'static str,
+    pub help_switch_previous: &'static str,
+    pub unknown_setting: &'static str,
+    pub help_config_desc: &'static str,
+    pub help_opt_show_origin: &'static str,
+    pub help_opt_config_scope: &'static str,
+    pub help_opt_override: &'static str,
+    pub config_set_done: &'static str,
+    pub config_unset_done: &'static str,
+    pub config_write_error: &'static str,
+    pub config_newer_version: &'static str,
+    pub config_migration_failed: &'static str,
+    pub wt_path_invalid: &'static str,
+    pub wt_path_overlaps: &'static str,
+    pub hook_running: &'static str,
+    pub hook_failed: &'static str,
+    pub hook_exit_code: &'static str,
+    pub hook_timed_out: &'static str,
+    pub hook_review: &'static str,
+    pub hook_trust_prompt: &'static str,
+    pub hook_untrusted: &'static str,
+    pub hook_rollback_prompt: &'static str,
+    pub hook_kept: &'static str,
+    pub hook_rolled_back: &'static str,
+    pub hook_remove_aborted: &'static str,
+    pub branch_delete_failed: &'static str,
+    pub local_files: &'static str,
+    pub seed_copied: &'static str,
+    pub seed_hardlinked: &'static str,
+    pub seed_symlinked: &'static str,
+    pub seed_skipped: &'static str,
+    pub seed_failed: &'static str,
+    pub seed_cloning: &'static str,
+    pub seed_cloned: &'static str,
+    pub config_personal_setting: &'static str,
+    pub ai_no_api_key: &'static str,
+    pub ai_failed: &'static str,
+    pub settings_backend: &'static str,
+    pub backend_set: &'static str,
+    pub model_prompt: &'static str,
+    pub name_invalid: &'static str,
+    pub name_too_long: &'static str,
+    pub name_missing_prefix: &'static str,
+    pub name_exists: &'static str,
+    pub name_exists_on_remote: &'static str,
+    pub no_usable_suggestion: &'static str,
+    pub help_opt_show_prompt: &'static str,
+    pub prompt_template_ignored: &'static str,
+    pub template_unclosed: &'static str,
+    pub template_unknown: &'static str,
+    pub template_missing: &'static str,
+    pub prompt_template_outside: &'static str,
+    pub pr_branch_kept: &'static str,
+    pub ai_no_curl: &'static str,
+    pub unknown_remote: &'static str,
+    pub upstream_differs: &'static str,
+    pub upstream_set_failed: &'static str,
+    pub list_format_invalid: &'static str,
 }
 
 include!(concat!(env!("OUT_DIR"), "/i18n_generated.rs"));
@@ -1,10 +1,20 @@
 mod ai;
 mod banner;
+mod cli;
 mod commands;
 mod config;
+mod fuzzy;
 mod git;
+mod glob;
+mod hooks;
 mod i18n;
 mod menu;
+mod naming;
+mod output;
+mod seed;
+mod shell;
+mod state;
+mod template;
 
 pub const RED: &str = "\x1b[0;31m";
 pub const GREEN: &str = "\x1b[0;32m";
@@ -17,10 +27,16 @@ pub const NC: &str = "\x1b[0m";
 
 pub const VERSION: &str = "0.0.1";
 
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_CANCELLED: i32 = 130;
+
 pub struct App {
     #[allow(dead_code)]
     pub lang: i18n::Lang,
     pub messages: &'static i18n::Messages,
+    /// Settings as loaded at startup
+    pub config: config::Config,
 }
 
 pub fn err(msg: &str) {
@@ -36,32 +52,52 @@ pub fn success(msg: &str) {
 }
 
 fn main() {
-    config::check_first_run();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = cli::split_overrides(&args).and_then(|(overrides, rest)| {
+        config::set_overrides(overrides);
+        cli::parse(rest)
+    });
+
+    let mut config = config::Config::load();
+    // `shuu init` runs from shell startup files and must never prompt
+    if !matches!(command, Ok(cli::Command::Init { .. }) | Err(_))
+        && config::check_first_run(&config)
+    {
+        config = config::Config::load();
+    }
 
-    let lang = config::resolve_lang();
+    let lang = config::resolve_lang(&config);
     let messages = i18n::messages(lang);
-    let app = App { lang, messages };
+    let app = App {
+        lang,
+        messages,
+        config,
+    };
 
-    let args: Vec<String> = std::env::args().collect();
+    for warning in &app.config.warnings {
+        eprintln!("{}warning:{} {}", YELLOW, NC, warning.describe(messages));
+    }
 
-    match args.get(1).map(|s| s.as_str()) {
-        None => commands::cmd_interactive(&app),
-        Some("create" | "c") => {
-            let desc = if args.len() > 2 {
-                args[2..].join(" ")
-            } else {
-                String::new()
-            };
-            commands::cmd_create(&app, desc);
-        }
-        Some("list" | "l" | "ls") => commands::cmd_list(&app),
-        Some("remove" | "rm") => commands::cmd_remove(&app),
-        Some("switch" | "s") => commands::cmd_switch(&app),
-        Some("settings") => commands::cmd_settings(&app),
-        Some("help" | "-h" | "--help") => commands::cmd_help(&app),
-        Some(_) => {
-            let desc = args[1..].join(" ");
-            commands::cmd_create(&app, desc);
+    let command = match command {
+        Ok(c) => c,
+        Err(e) => {
+            err(&e.describe(messages));
+            eprintln!("{}", messages.see_help);
+            std::process::exit(EXIT_USAGE);
         }
+    };
+
+    match command {
+        cli::Command::Interactive => commands::cmd_interactive(&app),
+        cli::Command::Create(create) => commands::cmd_create(&app, create),
+        cli::Command::Checkout { target, remote } => commands::cmd_checkout(&app, target, remote),
+        cli::Command::List(format) => commands::cmd_list(&app, format),
+        cli::Command::Remove => commands::cmd_remove(&app),
+        cli::Command::Clean(clean) => commands::cmd_clean(&app, clean),
+        cli::Command::Switch { query } => commands::cmd_switch(&app, query.as_deref()),
+        cli::Command::Settings => commands::cmd_settings(&app),
+        cli::Command::Config(action) => commands::cmd_config(&app, action),
+        cli::Command::Init { shell } => commands::cmd_init(&app, shell.as_deref()),
+        cli::Command::Help => commands::cmd_help(&app),
     }
 }
@@ -1,10 +1,51 @@
+use crate::fuzzy;
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode},
+    terminal::{self, disable_raw_mode, enable_raw_mode},
 };
+use std::collections::HashMap;
 use std::io::{self, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// Rows kept free for the title, filter line and hint around the list
+const RESERVED_ROWS: usize = 4;
+
+/// Rows set aside for a preview, including its separator
+const PREVIEW_ROWS: usize = 12;
+
+/// Details shown under a menu for the highlighted item. Each item's lines
+/// are computed the first time it is highlighted and then reused.
+pub struct Preview<'a> {
+    render: Box<dyn FnMut(usize) -> Vec<String> + 'a>,
+    cache: HashMap<usize, Vec<String>>,
+    /// Toggled with Tab
+    shown: bool,
+}
+
+impl<'a> Preview<'a> {
+    pub fn new(render: impl FnMut(usize) -> Vec<String> + 'a) -> Self {
+        Self {
+            render: Box::new(render),
+            cache: HashMap::new(),
+            shown: true,
+        }
+    }
+
+    fn lines(&mut self, item: usize) -> &[String] {
+        let render = &mut self.render;
+        self.cache.entry(item).or_insert_with(|| render(item))
+    }
+}
+
+/// How a checklist was accepted
+pub struct Checklist {
+    /// Checked items in order; may be empty
+    pub checked: Vec<usize>,
+    /// The item under the cursor when Enter was pressed
+    pub highlighted: Option<usize>,
+}
 
 struct RawModeGuard;
 
@@ -24,9 +65,290 @@ impl Drop for RawModeGuard {
     }
 }
 
+/// What a key press asks a menu to do
+enum Action {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Toggle,
+    ToggleAll,
+    Accept,
+    /// `1`-`9` outside the filter, as a zero-based index
+    Jump(usize),
+    /// `/`: keys go to the filter query from now on
+    StartFilter,
+    /// A character added to the filter query
+    Type(char),
+    Erase,
+    /// Esc while filtering: drop the query and leave the filter
+    ClearFilter,
+    TogglePreview,
+    Cancel,
+    None,
+}
+
+fn next_action(filtering: bool) -> Action {
+    match event::read() {
+        Ok(Event::Key(ke)) if ke.kind == KeyEventKind::Press => key_action(ke, filtering),
+        Ok(_) => Action::None,
+        Err(_) => Action::Cancel,
+    }
+}
+
+/// Letter and digit bindings only apply outside the filter; while
+/// `filtering`, printable keys go to the query instead
+fn key_action(key: KeyEvent, filtering: bool) -> Action {
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Char('c') if control => Action::Cancel,
+        KeyCode::Char('p') if control => Action::Up,
+        KeyCode::Char('n') if control => Action::Down,
+        KeyCode::Char('a') if control => Action::ToggleAll,
+        KeyCode::Up => Action::Up,
+        KeyCode::Down => Action::Down,
+        KeyCode::PageUp => Action::PageUp,
+        KeyCode::PageDown => Action::PageDown,
+        KeyCode::Home => Action::Home,
+        KeyCode::End => Action::End,
+        KeyCode::Enter => Action::Accept,
+        KeyCode::Tab => Action::TogglePreview,
+        KeyCode::Backspace => Action::Erase,
+        KeyCode::Esc if filtering => Action::ClearFilter,
+        KeyCode::Esc => Action::Cancel,
+        KeyCode::Char(' ') => Action::Toggle,
+        KeyCode::Char(c) if filtering && !control => Action::Type(c),
+        KeyCode::Char(_) if control => Action::None,
+        KeyCode::Char('k') => Action::Up,
+        KeyCode::Char('j') => Action::Down,
+        KeyCode::Char('a') => Action::ToggleAll,
+        KeyCode::Char('q') => Action::Cancel,
+        KeyCode::Char('/') => Action::StartFilter,
+        KeyCode::Char(c @ '1'..='9') => Action::Jump((c as usize) - ('1' as usize)),
+        _ => Action::None,
+    }
+}
+
+/// Cuts `s` to at most `max` terminal columns, ending in `…` if shortened
+fn truncate(s: &str, max: usize) -> String {
+    let total: usize = s.chars().map(|c| c.width().unwrap_or(0)).sum();
+    if total <= max {
+        return s.to_string();
+    }
+
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > max {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    if max > 0 {
+        out.push('\u{2026}');
+    }
+    out
+}
+
+/// Filter, cursor and scroll state shared by both menus
+struct View<'a> {
+    items: &'a [String],
+    /// Whether keys go to `query`
+    filtering: bool,
+    query: String,
+    /// Indices into `items` that match the query, best first
+    visible: Vec<usize>,
+    /// Position in `visible`
+    cursor: usize,
+    /// First position in `visible` on screen
+    offset: usize,
+    /// Rows the list had at the last draw, for paging
+    page: usize,
+    /// Line breaks written by the last draw, to move back over it
+    drawn: usize,
+}
+
+impl<'a> View<'a> {
+    fn new(items: &'a [String]) -> Self {
+        Self {
+            items,
+            filtering: false,
+            query: String::new(),
+            visible: (0..items.len()).collect(),
+            cursor: 0,
+            offset: 0,
+            page: 1,
+            drawn: 0,
+        }
+    }
+
+    /// The highlighted item, as an index into `items`
+    fn current(&self) -> Option<usize> {
+        self.visible.get(self.cursor).copied()
+    }
+
+    fn refilter(&mut self) {
+        self.visible = fuzzy::filter(&self.query, self.items);
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    /// Applies movement and filter-editing actions. Returns false for
+    /// actions the menu itself has to handle.
+    fn handle(&mut self, action: &Action) -> bool {
+        let count = self.visible.len();
+        let last = count.saturating_sub(1);
+        match *action {
+            Action::Up if count > 0 => self.cursor = (self.cursor + count - 1) % count,
+            Action::Down if count > 0 => self.cursor = (self.cursor + 1) % count,
+            Action::PageUp => self.cursor = self.cursor.saturating_sub(self.page),
+            Action::PageDown => self.cursor = (self.cursor + self.page).min(last),
+            Action::Home => self.cursor = 0,
+            Action::End => self.cursor = last,
+            Action::Jump(n) if n < count => self.cursor = n,
+            Action::StartFilter => self.filtering = true,
+            Action::Type(c) => {
+                self.query.push(c);
+                self.refilter();
+            }
+            // Erasing past the start of the query leaves the filter
+            Action::Erase => match self.query.pop() {
+                Some(_) => self.refilter(),
+                None => self.filtering = false,
+            },
+            Action::ClearFilter => {
+                self.filtering = false;
+                self.query.clear();
+                self.refilter();
+            }
+            Action::Up | Action::Down | Action::Jump(_) | Action::None => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draws the filter line, the rows that fit the terminal and `hint`,
+    /// first moving back over the previous drawing. Every line is cut to the
+    /// terminal width so nothing wraps and the cursor-up count stays right.
+    fn draw(
+        &mut self,
+        out: &mut impl Write,
+        checked: Option<&[bool]>,
+        hint: &str,
+        preview: Option<&mut Preview>,
+    ) {
+        let (cols, rows) = terminal::size().unwrap_or((80, 24));
+        let width = (cols as usize).saturating_sub(1);
+        let rows = (rows as usize).saturating_sub(RESERVED_ROWS);
+
+        // The preview only gets space if the list keeps a few rows
+        let preview = preview.filter(|p| p.shown && rows >= PREVIEW_ROWS + 3);
+        let height = match preview {
+            Some(_) => rows - PREVIEW_ROWS,
+            None => rows.max(1),
+        };
+        self.page = height;
+
+        // Scroll just enough to keep the cursor on screen
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + height {
+            self.offset = self.cursor + 1 - height;
+        }
+
+        if self.drawn > 0 {
+            let _ = write!(out, "\r\x1b[{}A", self.drawn);
+        }
+        let _ = write!(out, "\r\x1b[J");
+        self.drawn = 0;
+
+        if self.filtering {
+            let caret = "\x1b[7m \x1b[0m";
+            let counts = format!("  ({}/{})", self.visible.len(), self.items.len());
+            let query = truncate(&self.query, width.saturating_sub(counts.len() + 5));
+            let _ = write!(
+                out,
+                "  \x1b[1;36m/\x1b[0m {}{}\x1b[2m{}\x1b[0m\r\n",
+                query, caret, counts
+            );
+            self.drawn += 1;
+        }
+
+        let end = (self.offset + height).min(self.visible.len());
+        for pos in self.offset..end {
+            let i = self.visible[pos];
+            let is_checked = checked.is_some_and(|c| c[i]);
+            let label = match checked {
+                Some(_) => format!("[{}] {}", if is_checked { "x" } else { " " }, self.items[i]),
+                None => self.items[i].clone(),
+            };
+            let label = truncate(&label, width.saturating_sub(4));
+            if pos == self.cursor {
+                let _ = write!(out, "  \x1b[0;32m\u{25b6} {}\x1b[0m\r\n", label);
+            } else if is_checked {
+                let _ = write!(out, "    {}\r\n", label);
+            } else {
+                let _ = write!(out, "  \x1b[2m  {}\x1b[0m\r\n", label);
+            }
+            self.drawn += 1;
+        }
+
+        let mut hint = format!("  {}", hint);
+        if self.visible.len() > height {
+            hint.push_str(&format!("  ({}/{})", self.cursor + 1, self.visible.len()));
+        }
+        let _ = write!(out, "\x1b[2m{}\x1b[0m", truncate(&hint, width));
+
+        if let (Some(preview), Some(item)) = (preview, self.current()) {
+            let _ = write!(
+                out,
+                "\r\n  \x1b[2m{}\x1b[0m",
+                "\u{2500}".repeat(width.min(60).saturating_sub(2))
+            );
+            self.drawn += 1;
+            for line in preview.lines(item).iter().take(PREVIEW_ROWS - 1) {
+                let _ = write!(out, "\r\n  {}", truncate(line, width.saturating_sub(2)));
+                self.drawn += 1;
+            }
+        }
+        let _ = out.flush();
+    }
+
+    /// Leaves the final drawing on screen, minus the hint line
+    fn finish(&self, out: &mut impl Write) {
+        let _ = write!(out, "\r\x1b[2K\r\n");
+        let _ = out.flush();
+    }
+}
+
 pub fn select_menu(title: &str, items: &[String], hint: &str) -> Option<usize> {
-    let total = items.len();
-    if total == 0 {
+    run_select(title, items, hint, "", None)
+}
+
+/// `select_menu` with a preview of the highlighted item below the list,
+/// starting filtered by `query` if it is not empty
+pub fn select_menu_with_preview(
+    title: &str,
+    items: &[String],
+    hint: &str,
+    query: &str,
+    preview: &mut Preview,
+) -> Option<usize> {
+    run_select(title, items, hint, query, Some(preview))
+}
+
+fn run_select(
+    title: &str,
+    items: &[String],
+    hint: &str,
+    query: &str,
+    mut preview: Option<&mut Preview>,
+) -> Option<usize> {
+    if items.is_empty() {
         return None;
     }
 
@@ -36,86 +358,218 @@ pub fn select_menu(title: &str, items: &[String], hint: &str) -> Option<usize> {
     };
 
     let mut stderr = io::stderr();
-    let mut selected: usize = 0;
+    let mut view = View::new(items);
+    if !query.is_empty() {
+        view.filtering = true;
+        view.query = query.to_string();
+        view.refilter();
+    }
 
     // Print title
     let _ = write!(stderr, "\r\n\x1b[1m{}\x1b[0m\r\n", title);
 
-    let mut redraw = false;
     loop {
-        if redraw {
-            let _ = write!(stderr, "\r\x1b[{}A", total);
-        }
+        view.draw(&mut stderr, None, hint, preview.as_deref_mut());
 
-        for (i, item) in items.iter().enumerate() {
-            let _ = write!(stderr, "\x1b[2K");
-            if i == selected {
-                let _ = write!(stderr, "  \x1b[0;32m\u{25b6} {}\x1b[0m\r\n", item);
-            } else {
-                let _ = write!(stderr, "  \x1b[2m  {}\x1b[0m\r\n", item);
+        let action = next_action(view.filtering);
+        // Digits pick an item outright
+        let accept = match action {
+            Action::Accept => true,
+            Action::Jump(n) => n < view.visible.len(),
+            _ => false,
+        };
+        if !view.handle(&action) {
+            match action {
+                Action::Cancel => {
+                    view.finish(&mut stderr);
+                    return None;
+                }
+                Action::TogglePreview => toggle(&mut preview),
+                _ => {}
             }
         }
-        let _ = write!(stderr, "\x1b[2K\x1b[2m  {}\x1b[0m", hint);
-        let _ = stderr.flush();
+        if !accept {
+            continue;
+        }
 
-        redraw = true;
+        if let Some(selected) = view.current() {
+            // Final redraw to show selection
+            view.draw(&mut stderr, None, hint, None);
+            view.finish(&mut stderr);
+            return Some(selected);
+        }
+    }
+}
 
-        let event = match event::read() {
-            Ok(e) => e,
-            Err(_) => return None,
-        };
+/// Checklist variant of `select_menu`: space toggles an item, Ctrl+A selects
+/// all or none of the shown items. Returns None if cancelled.
+pub fn multi_select_menu(
+    title: &str,
+    items: &[String],
+    preselected: &[usize],
+    hint: &str,
+) -> Option<Checklist> {
+    run_multi_select(title, items, preselected, hint, None)
+}
 
-        let key_event = match event {
-            Event::Key(ke) if ke.kind == KeyEventKind::Press => ke,
-            _ => continue,
-        };
+/// `multi_select_menu` with a preview of the highlighted item below the list
+pub fn multi_select_menu_with_preview(
+    title: &str,
+    items: &[String],
+    preselected: &[usize],
+    hint: &str,
+    preview: &mut Preview,
+) -> Option<Checklist> {
+    run_multi_select(title, items, preselected, hint, Some(preview))
+}
 
-        let mut accept = false;
+fn run_multi_select(
+    title: &str,
+    items: &[String],
+    preselected: &[usize],
+    hint: &str,
+    mut preview: Option<&mut Preview>,
+) -> Option<Checklist> {
+    let total = items.len();
+    if total == 0 {
+        return Some(Checklist {
+            checked: Vec::new(),
+            highlighted: None,
+        });
+    }
 
-        match key_event.code {
-            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                let _ = write!(stderr, "\r\n");
-                let _ = stderr.flush();
-                return None;
-            }
-            KeyCode::Up | KeyCode::Char('k') => {
-                selected = (selected + total - 1) % total;
-            }
-            KeyCode::Down | KeyCode::Char('j') => {
-                selected = (selected + 1) % total;
+    let _guard = RawModeGuard::new().ok()?;
+
+    let mut stderr = io::stderr();
+    let mut view = View::new(items);
+    let mut checked: Vec<bool> = (0..total).map(|i| preselected.contains(&i)).collect();
+
+    let _ = write!(stderr, "\r\n\x1b[1m{}\x1b[0m\r\n", title);
+
+    loop {
+        view.draw(&mut stderr, Some(&checked), hint, preview.as_deref_mut());
+
+        let action = next_action(view.filtering);
+        if view.handle(&action) {
+            continue;
+        }
+        match action {
+            Action::Toggle => {
+                if let Some(i) = view.current() {
+                    checked[i] = !checked[i];
+                }
             }
-            KeyCode::Enter => {
-                accept = true;
+            Action::ToggleAll => {
+                let all = view.visible.iter().all(|&i| checked[i]);
+                for &i in &view.visible {
+                    checked[i] = !all;
+                }
             }
-            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
-                let _ = write!(stderr, "\r\n");
-                let _ = stderr.flush();
+            Action::TogglePreview => toggle(&mut preview),
+            Action::Accept => break,
+            Action::Cancel => {
+                view.finish(&mut stderr);
                 return None;
             }
-            KeyCode::Char(c @ '1'..='9') => {
-                let num = (c as usize) - ('1' as usize);
-                if num < total {
-                    selected = num;
-                    accept = true;
-                }
-            }
             _ => {}
         }
+    }
 
-        if accept {
-            // Final redraw to show selection
-            let _ = write!(stderr, "\r\x1b[{}A", total);
-            for (i, item) in items.iter().enumerate() {
-                let _ = write!(stderr, "\x1b[2K");
-                if i == selected {
-                    let _ = write!(stderr, "  \x1b[0;32m\u{25b6} {}\x1b[0m\r\n", item);
-                } else {
-                    let _ = write!(stderr, "  \x1b[2m  {}\x1b[0m\r\n", item);
-                }
-            }
-            let _ = write!(stderr, "\x1b[2K\r\n");
-            let _ = stderr.flush();
-            return Some(selected);
+    // Drop the preview so only the list stays on screen
+    view.draw(&mut stderr, Some(&checked), hint, None);
+    view.finish(&mut stderr);
+    Some(Checklist {
+        checked: (0..total).filter(|&i| checked[i]).collect(),
+        highlighted: view.current(),
+    })
+}
+
+fn toggle(preview: &mut Option<&mut Preview>) {
+    if let Some(p) = preview {
+        p.shown = !p.shown;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_key_action_letters_outside_the_filter() {
+        assert!(matches!(
+            key_action(key(KeyCode::Char('q')), false),
+            Action::Cancel
+        ));
+        assert!(matches!(
+            key_action(key(KeyCode::Char('a')), false),
+            Action::ToggleAll
+        ));
+        assert!(matches!(
+            key_action(key(KeyCode::Char('j')), false),
+            Action::Down
+        ));
+        assert!(matches!(
+            key_action(key(KeyCode::Char('k')), false),
+            Action::Up
+        ));
+        assert!(matches!(
+            key_action(key(KeyCode::Char('2')), false),
+            Action::Jump(1)
+        ));
+        assert!(matches!(
+            key_action(key(KeyCode::Char('/')), false),
+            Action::StartFilter
+        ));
+        assert!(matches!(
+            key_action(key(KeyCode::Char('x')), false),
+            Action::None
+        ));
+    }
+
+    #[test]
+    fn test_key_action_filter_takes_printable_keys() {
+        for c in ['q', 'a', 'j', '1', '/'] {
+            assert!(matches!(
+                key_action(key(KeyCode::Char(c)), true),
+                Action::Type(t) if t == c
+            ));
         }
+        assert!(matches!(
+            key_action(key(KeyCode::Char(' ')), true),
+            Action::Toggle
+        ));
+    }
+
+    #[test]
+    fn test_key_action_esc_clears_query_before_cancelling() {
+        assert!(matches!(
+            key_action(key(KeyCode::Esc), true),
+            Action::ClearFilter
+        ));
+        assert!(matches!(
+            key_action(key(KeyCode::Esc), false),
+            Action::Cancel
+        ));
+        let ctrl_a = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+        assert!(matches!(key_action(ctrl_a, true), Action::ToggleAll));
+    }
+
+    #[test]
+    fn test_truncate_fits() {
+        assert_eq!(truncate("feat/login", 10), "feat/login");
+        assert_eq!(truncate("feat/login", 8), "feat/lo\u{2026}");
+        assert_eq!(truncate("abc", 0), "");
+    }
+
+    #[test]
+    fn test_truncate_wide_characters() {
+        // Each kana takes two columns
+        assert_eq!(truncate("ブランチ", 8), "ブランチ");
+        assert_eq!(truncate("ブランチ", 7), "ブラン\u{2026}");
+        assert_eq!(truncate("ブランチ", 6), "ブラ\u{2026}");
     }
 }
@@ -0,0 +1,372 @@
+use crate::config::{self, Config};
+use crate::git;
+use crate::i18n::Messages;
+
+/// Rules for new branch names on top of git's own
+#[derive(Debug, Default)]
+pub struct Rules {
+    /// Most characters a name may have (`branch.max_length`)
+    pub max_length: Option<usize>,
+    /// Allowed prefixes such as `feat/` (`branch.prefixes`). Empty allows any.
+    pub prefixes: Vec<String>,
+}
+
+impl Rules {
+    pub fn load(config: &Config) -> Rules {
+        Rules {
+            max_length: config.get("branch.max_length").and_then(|n| n.parse().ok()),
+            prefixes: config
+                .get("branch.prefixes")
+                .map(config::split_list)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Why a name can't be used for a new branch
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Empty,
+    /// Refused by `git check-ref-format --branch`
+    InvalidRef,
+    TooLong(usize),
+    /// The allowed prefixes, joined for display
+    MissingPrefix(String),
+    /// Already a local branch
+    Exists,
+    /// Already a branch on this remote
+    ExistsOnRemote(String),
+}
+
+impl Problem {
+    pub fn describe(&self, name: &str, m: &Messages) -> String {
+        match self {
+            Problem::Empty => m.enter_branch_name.to_string(),
+            Problem::InvalidRef => m.name_invalid.replacen("%s", name, 1),
+            Problem::TooLong(max) => {
+                m.name_too_long
+                    .replacen("%s", name, 1)
+                    .replacen("%s", &max.to_string(), 1)
+            }
+            Problem::MissingPrefix(prefixes) => m
+                .name_missing_prefix
+                .replacen("%s", name, 1)
+                .replacen("%s", prefixes, 1),
+            Problem::Exists => m.name_exists.replacen("%s", name, 1),
+            Problem::ExistsOnRemote(remote) => m
+                .name_exists_on_remote
+                .replacen("%s", name, 1)
+                .replacen("%s", remote, 1),
+        }
+    }
+}
+
+/// Branches a new name must not clash with
+pub struct Existing {
+    local: Vec<String>,
+    remotes: Vec<String>,
+    remote_branches: Vec<String>,
+}
+
+impl Existing {
+    pub fn load() -> Existing {
+        Existing {
+            local: git::local_branches(),
+            remotes: git::remotes(),
+            remote_branches: git::remote_branches(),
+        }
+    }
+
+    fn has_local(&self, name: &str) -> bool {
+        self.local.iter().any(|b| b == name)
+    }
+
+    fn clash(&self, name: &str) -> Option<Problem> {
+        if self.has_local(name) {
+            return Some(Problem::Exists);
+        }
+        self.remotes
+            .iter()
+            .find(|r| {
+                self.remote_branches.iter().any(|b| {
+                    b.strip_prefix(r.as_str()).and_then(|b| b.strip_prefix('/')) == Some(name)
+                })
+            })
+            .map(|r| Problem::ExistsOnRemote(r.clone()))
+    }
+}
+
+/// Whether the text before a `:` reads as a label such as `Branch name` or
+/// `**Suggestion**` rather than a commit type such as `feat`
+fn is_label(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    text.chars()
+        .all(|c| c.is_alphabetic() || c.is_whitespace() || c == '*')
+        && (text.contains(|c: char| c.is_whitespace() || c == '*')
+            || lower.contains("branch")
+            || lower.contains("name"))
+}
+
+/// What models tend to put around a name: a label such as `Branch name:`,
+/// quotes, backticks, bold markers and a closing period. A commit-style
+/// `feat: add-login` becomes `feat/add-login`.
+pub fn strip_wrappers(s: &str) -> String {
+    let mut s = s.trim();
+    // Git refuses `:`, so text before one is never part of the name
+    if let Some((label, rest)) = s.split_once(':') {
+        if is_label(label) {
+            s = rest.trim();
+        }
+    }
+    let s = s
+        .trim_end_matches('.')
+        .trim_matches(['`', '"', '\'', '*'])
+        .trim_end_matches('.')
+        .trim();
+    match s.split_once(':') {
+        Some((kind, rest))
+            if !kind.is_empty()
+                && kind
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') =>
+        {
+            format!("{}/{}", kind, rest.trim_start())
+        }
+        _ => s.to_string(),
+    }
+}
+
+/// Best-effort repair of a suggested name: wrappers stripped, characters git
+/// refuses replaced by `-`, the first allowed prefix added if none is there,
+/// and shortened to the maximum length
+pub fn sanitize(name: &str, rules: &Rules) -> String {
+    let mut cleaned = String::new();
+    for c in strip_wrappers(name).chars() {
+        let c = match c {
+            '~' | '^' | ':' | '?' | '*' | '[' | '\\' | '{' | '}' => '-',
+            c if c.is_whitespace() || c.is_control() => '-',
+            c => c,
+        };
+        let doubled = matches!(c, '-' | '/' | '.') && cleaned.ends_with(c);
+        if !doubled {
+            cleaned.push(c);
+        }
+    }
+
+    let mut name = cleaned
+        .split('/')
+        .map(|part| {
+            part.trim_start_matches('.')
+                .trim_end_matches(".lock")
+                .trim_matches('-')
+        })
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    name = name.trim_end_matches('.').to_string();
+
+    if !rules.prefixes.is_empty() && !rules.prefixes.iter().any(|p| name.starts_with(p.as_str())) {
+        name = format!("{}{}", rules.prefixes[0], name);
+    }
+    if let Some(max) = rules.max_length {
+        name = shorten(&name, max);
+    }
+    name
+}
+
+/// At most `max` characters, not ending in a separator
+fn shorten(name: &str, max: usize) -> String {
+    name.chars()
+        .take(max)
+        .collect::<String>()
+        .trim_end_matches(['-', '/', '.'])
+        .to_string()
+}
+
+/// Checks `name` against git and `rules`, but not existing branches
+pub fn check(name: &str, rules: &Rules) -> Result<(), Problem> {
+    if name.is_empty() {
+        return Err(Problem::Empty);
+    }
+    if !git::check_branch_name(name) {
+        return Err(Problem::InvalidRef);
+    }
+    if let Some(max) = rules.max_length {
+        if name.chars().count() > max {
+            return Err(Problem::TooLong(max));
+        }
+    }
+    if !rules.prefixes.is_empty() && !rules.prefixes.iter().any(|p| name.starts_with(p.as_str())) {
+        return Err(Problem::MissingPrefix(rules.prefixes.join(", ")));
+    }
+    Ok(())
+}
+
+/// Checks a name the user typed. An existing local branch is reused as it
+/// is; a new one must pass `check` and not be taken on a remote.
+pub fn check_chosen(name: &str, rules: &Rules, existing: &Existing) -> Result<(), Problem> {
+    if existing.has_local(name) {
+        return Ok(());
+    }
+    check(name, rules)?;
+    match existing.clash(name) {
+        Some(problem) => Err(problem),
+        None => Ok(()),
+    }
+}
+
+/// `suggestion` made into a valid new branch name. A name that is taken
+/// gets a numeric suffix such as `-2`.
+pub fn correct(suggestion: &str, rules: &Rules, existing: &Existing) -> Result<String, Problem> {
+    let name = sanitize(suggestion, rules);
+    check(&name, rules)?;
+    let clash = match existing.clash(&name) {
+        None => return Ok(name),
+        Some(problem) => problem,
+    };
+
+    for n in 2..10 {
+        let suffix = format!("-{}", n);
+        let base = match rules.max_length {
+            Some(max) => shorten(&name, max.saturating_sub(suffix.len())),
+            None => name.clone(),
+        };
+        let candidate = format!("{}{}", base, suffix);
+        if check(&candidate, rules).is_ok() && existing.clash(&candidate).is_none() {
+            return Ok(candidate);
+        }
+    }
+    Err(clash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn existing() -> Existing {
+        Existing {
+            local: vec!["main".into(), "feat/login".into(), "feat/login-2".into()],
+            remotes: vec!["origin".into()],
+            remote_branches: vec!["origin/main".into(), "origin/fix/typo".into()],
+        }
+    }
+
+    fn rules(max_length: Option<usize>, prefixes: &[&str]) -> Rules {
+        Rules {
+            max_length,
+            prefixes: prefixes.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_strip_wrappers() {
+        assert_eq!(strip_wrappers("`feat/login`"), "feat/login");
+        assert_eq!(strip_wrappers("Branch name: \"feat/login\"."), "feat/login");
+        assert_eq!(strip_wrappers("**Branch:** feat/login"), "feat/login");
+        assert_eq!(strip_wrappers("  'fix/typo'  "), "fix/typo");
+        assert_eq!(strip_wrappers("Name: add-login"), "add-login");
+    }
+
+    #[test]
+    fn test_strip_wrappers_keeps_commit_types() {
+        assert_eq!(strip_wrappers("feat: add-login"), "feat/add-login");
+        assert_eq!(strip_wrappers("`fix: typo`"), "fix/typo");
+        assert_eq!(strip_wrappers("Branch: feat: add-login"), "feat/add-login");
+        assert_eq!(
+            sanitize("feat: add login", &rules(None, &["feat/", "fix/"])),
+            "feat/add-login"
+        );
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let none = Rules::default();
+        assert_eq!(
+            sanitize("feat/add login page", &none),
+            "feat/add-login-page"
+        );
+        assert_eq!(sanitize("feat//x..y~1^2:z", &none), "feat/x.y-1-2-z");
+        assert_eq!(sanitize("/.hidden/name.lock.", &none), "hidden/name");
+        assert_eq!(
+            sanitize("add-login", &rules(None, &["feat/", "fix/"])),
+            "feat/add-login"
+        );
+        assert_eq!(
+            sanitize("fix/typo", &rules(None, &["feat/", "fix/"])),
+            "fix/typo"
+        );
+        assert_eq!(
+            sanitize("feat/add-login-page", &rules(Some(14), &[])),
+            "feat/add-login"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let none = Rules::default();
+        assert_eq!(check("feat/login", &none), Ok(()));
+        assert_eq!(check("", &none), Err(Problem::Empty));
+        assert_eq!(check("feat/a b", &none), Err(Problem::InvalidRef));
+        assert_eq!(check("-x", &none), Err(Problem::InvalidRef));
+        assert_eq!(
+            check("feat/login", &rules(Some(5), &[])),
+            Err(Problem::TooLong(5))
+        );
+        assert_eq!(
+            check("login", &rules(None, &["feat/", "fix/"])),
+            Err(Problem::MissingPrefix("feat/, fix/".into()))
+        );
+    }
+
+    #[test]
+    fn test_correct_avoids_existing_branches() {
+        let none = Rules::default();
+        let existing = existing();
+        assert_eq!(
+            correct("`feat/signup`", &none, &existing),
+            Ok("feat/signup".into())
+        );
+        assert_eq!(
+            correct("feat/login", &none, &existing),
+            Ok("feat/login-3".into())
+        );
+        assert_eq!(
+            correct("fix/typo", &none, &existing),
+            Ok("fix/typo-2".into())
+        );
+        assert_eq!(
+            correct("feat/login", &rules(Some(11), &[]), &existing),
+            Ok("feat/logi-2".into())
+        );
+        assert_eq!(correct("  ", &none, &existing), Err(Problem::Empty));
+    }
+
+    #[test]
+    fn test_clash() {
+        let existing = existing();
+        assert_eq!(existing.clash("main"), Some(Problem::Exists));
+        assert_eq!(
+            existing.clash("fix/typo"),
+            Some(Problem::ExistsOnRemote("origin".into()))
+        );
+        assert_eq!(existing.clash("feat/new"), None);
+    }
+
+    #[test]
+    fn test_check_chosen() {
+        let strict = rules(Some(4), &["feat/"]);
+        assert_eq!(check_chosen("main", &strict, &existing()), Ok(()));
+        assert_eq!(
+            check_chosen("other", &strict, &existing()),
+            Err(Problem::TooLong(4))
+        );
+        assert_eq!(
+            check_chosen("fix/typo", &Rules::default(), &existing()),
+            Err(Problem::ExistsOnRemote("origin".into()))
+        );
+        assert_eq!(
+            check_chosen("fix/new", &Rules::default(), &existing()),
+            Ok(())
+        );
+    }
+}
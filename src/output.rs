@@ -0,0 +1,317 @@
+use crate::git::{Worktree, WorktreeStatus};
+use crate::template::{self, TemplateError};
+use serde_json::{json, Value};
+
+/// A worktree as reported by `shuu list` in machine-readable modes
+pub struct Entry<'a> {
+    pub wt: &'a Worktree,
+    pub is_main: bool,
+    pub is_current: bool,
+    /// None if the status could not be read (bare or prunable worktrees)
+    pub status: Option<&'a WorktreeStatus>,
+}
+
+/// Compact age such as `45s`, `5m`, `3h`, `2d`, `3w`, `4mo` or `2y`
+pub fn relative_age(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (value, unit) = match secs {
+        s if s < 60 => (s, "s"),
+        s if s < 3600 => (s / 60, "m"),
+        s if s < 86400 => (s / 3600, "h"),
+        s if s < 86400 * 14 => (s / 86400, "d"),
+        s if s < 86400 * 60 => (s / (86400 * 7), "w"),
+        s if s < 86400 * 365 => (s / (86400 * 30), "mo"),
+        s => (s / (86400 * 365), "y"),
+    };
+    format!("{}{}", value, unit)
+}
+
+/// Size such as `512B`, `4.0K`, `12M` or `1.3G`, in powers of 1024
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 {
+        format!("{:.1}{}", size, UNITS[unit])
+    } else {
+        format!("{:.0}{}", size, UNITS[unit])
+    }
+}
+
+/// `YYYY-MM-DD` for a Unix timestamp, in UTC
+pub fn iso_date(secs: i64) -> String {
+    // Civil-from-days, after Howard Hinnant's date algorithms
+    let days = secs.div_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Seconds the local timezone is ahead of UTC at `secs`
+pub fn utc_offset(secs: i64) -> i64 {
+    let t = secs as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm we pass it
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&t, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_gmtoff
+    }
+}
+
+/// Template used by `--porcelain`
+pub const PORCELAIN_FORMAT: &str = "{path}\\t{branch}\\t{head}\\t{flags}";
+
+impl Entry<'_> {
+    fn dirty(&self) -> Option<bool> {
+        self.status.map(|s| s.is_dirty())
+    }
+
+    fn status_json(status: &WorktreeStatus) -> Value {
+        let pair = |ab: Option<(usize, usize)>| ab.map(|(a, b)| json!({"ahead": a, "behind": b}));
+        json!({
+            "modified": status.modified,
+            "untracked": status.untracked,
+            "upstream": status.upstream,
+            "upstream_gone": status.upstream_gone(),
+            "upstream_ahead_behind": pair(status.upstream_ahead_behind),
+            "main_ahead_behind": pair(status.main_ahead_behind),
+            "merged": status.merged(),
+            "last_commit": {
+                "subject": status.last_commit_subject,
+                "time": status.last_commit_time,
+            },
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "path": self.wt.path,
+            "branch": self.wt.branch_name(),
+            "head": self.wt.head,
+            "detached": self.wt.detached,
+            "bare": self.wt.bare,
+            "main": self.is_main,
+            "current": self.is_current,
+            "locked": self.wt.locked.is_some(),
+            "locked_reason": self.wt.locked.as_deref().filter(|r| !r.is_empty()),
+            "prunable": self.wt.prunable.is_some(),
+            "prunable_reason": self.wt.prunable,
+            "dirty": self.dirty(),
+            "status": self.status.map(Self::status_json),
+        })
+    }
+
+    /// Comma-separated state flags, or `-` if none apply
+    fn flags(&self) -> String {
+        let flags: Vec<&str> = [
+            (self.is_main, "main"),
+            (self.is_current, "current"),
+            (self.wt.detached, "detached"),
+            (self.wt.bare, "bare"),
+            (self.wt.locked.is_some(), "locked"),
+            (self.wt.prunable.is_some(), "prunable"),
+            (self.dirty() == Some(true), "dirty"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| *name)
+        .collect();
+
+        if flags.is_empty() {
+            "-".to_string()
+        } else {
+            flags.join(",")
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let yes_no = |b: bool| if b { "yes" } else { "no" }.to_string();
+        let value = match name {
+            "path" => self.wt.path.clone(),
+            "branch" => self.wt.branch_name().unwrap_or("").to_string(),
+            "head" => self.wt.head.clone(),
+            "short_head" => self.wt.short_head().to_string(),
+            "main" => yes_no(self.is_main),
+            "current" => yes_no(self.is_current),
+            "locked" => yes_no(self.wt.locked.is_some()),
+            "prunable" => yes_no(self.wt.prunable.is_some()),
+            "dirty" => match self.dirty() {
+                Some(d) => yes_no(d),
+                None => String::new(),
+            },
+            "flags" => self.flags(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Renders a `--format` template: `{field}` placeholders, `{{`/`}}` for
+    /// literal braces and the escapes `\t`, `\n` and `\\`
+    pub fn render(&self, template: &str) -> Result<String, TemplateError> {
+        template::render(&template::unescape(template), |name| self.field(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worktree() -> Worktree {
+        let mut wt = Worktree::default();
+        wt.path = "/repo-worktrees/feat-x".to_string();
+        wt.head = "9f45081f150a6b488c2693df1c8abb7e2f109a39".to_string();
+        wt.branch = Some("refs/heads/feat/x".to_string());
+        wt
+    }
+
+    static DIRTY: WorktreeStatus = WorktreeStatus {
+        modified: 1,
+        untracked: 0,
+        upstream: None,
+        upstream_ahead_behind: None,
+        main_ahead_behind: Some((2, 0)),
+        last_commit_subject: String::new(),
+        last_commit_time: 0,
+    };
+
+    fn entry(wt: &Worktree) -> Entry<'_> {
+        Entry {
+            wt,
+            is_main: false,
+            is_current: true,
+            status: Some(&DIRTY),
+        }
+    }
+
+    #[test]
+    fn test_render_fields_and_escapes() {
+        let wt = worktree();
+        let e = entry(&wt);
+        assert_eq!(
+            e.render("{path}\\t{branch}\\t{short_head}\\n"),
+            Ok("/repo-worktrees/feat-x\tfeat/x\t9f45081\n".to_string())
+        );
+        assert_eq!(
+            e.render("{current}/{main}/{dirty}"),
+            Ok("yes/no/yes".to_string())
+        );
+        assert_eq!(e.render("{{{branch}}}"), Ok("{feat/x}".to_string()));
+    }
+
+    #[test]
+    fn test_render_unknown_and_unclosed_placeholders() {
+        let wt = worktree();
+        let e = entry(&wt);
+        assert_eq!(
+            e.render("{nope} {branch}"),
+            Err(TemplateError::UnknownPlaceholder("nope".to_string()))
+        );
+        assert_eq!(e.render("{branch"), Err(TemplateError::Unclosed));
+    }
+
+    #[test]
+    fn test_porcelain_flags() {
+        let mut wt = worktree();
+        wt.locked = Some(String::new());
+        let e = entry(&wt);
+        assert_eq!(
+            e.render(PORCELAIN_FORMAT),
+            Ok(format!(
+                "/repo-worktrees/feat-x\tfeat/x\t{}\tcurrent,locked,dirty",
+                wt.head
+            ))
+        );
+
+        let clean = Entry {
+            wt: &worktree(),
+            is_main: false,
+            is_current: false,
+            status: Some(&WorktreeStatus::default()),
+        }
+        .render("{flags}");
+        assert_eq!(clean, Ok("-".to_string()));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut wt = worktree();
+        wt.locked = Some("on usb".to_string());
+        let v = entry(&wt).to_json();
+        assert_eq!(v["path"], "/repo-worktrees/feat-x");
+        assert_eq!(v["branch"], "feat/x");
+        assert_eq!(v["current"], true);
+        assert_eq!(v["main"], false);
+        assert_eq!(v["locked"], true);
+        assert_eq!(v["locked_reason"], "on usb");
+        assert_eq!(v["prunable"], false);
+        assert!(v["prunable_reason"].is_null());
+        assert_eq!(v["dirty"], true);
+        assert_eq!(v["status"]["modified"], 1);
+        assert_eq!(v["status"]["merged"], false);
+        assert_eq!(v["status"]["main_ahead_behind"]["ahead"], 2);
+        assert!(v["status"]["upstream_ahead_behind"].is_null());
+    }
+
+    #[test]
+    fn test_relative_age() {
+        assert_eq!(relative_age(-5), "0s");
+        assert_eq!(relative_age(45), "45s");
+        assert_eq!(relative_age(300), "5m");
+        assert_eq!(relative_age(3 * 3600), "3h");
+        assert_eq!(relative_age(2 * 86400), "2d");
+        assert_eq!(relative_age(21 * 86400), "3w");
+        assert_eq!(relative_age(120 * 86400), "4mo");
+        assert_eq!(relative_age(800 * 86400), "2y");
+    }
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(iso_date(0), "1970-01-01");
+        assert_eq!(iso_date(951782400), "2000-02-29");
+        assert_eq!(iso_date(1792281599), "2026-10-17");
+        assert_eq!(iso_date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512B");
+        assert_eq!(human_size(4096), "4.0K");
+        assert_eq!(human_size(12 * 1024 * 1024), "12M");
+        assert_eq!(human_size(1395864371), "1.3G");
+    }
+
+    #[test]
+    fn test_to_json_detached_has_null_branch() {
+        let mut wt = Worktree::default();
+        wt.path = "/det".to_string();
+        wt.detached = true;
+        let v = Entry {
+            wt: &wt,
+            is_main: false,
+            is_current: false,
+            status: None,
+        }
+        .to_json();
+        assert!(v["branch"].is_null());
+        assert!(v["dirty"].is_null());
+        assert!(v["status"].is_null());
+        assert_eq!(v["detached"], true);
+    }
+}
@@ -0,0 +1,465 @@
+use crate::config::{self, Config};
+use crate::glob;
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+/// How an untracked file from the main worktree gets into a new one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Copy,
+    Hardlink,
+    Symlink,
+    /// Copy-on-write copy where the filesystem supports it, for heavy
+    /// build directories
+    Clone,
+}
+
+impl Method {
+    pub const ALL: [Method; 4] = [
+        Method::Copy,
+        Method::Hardlink,
+        Method::Symlink,
+        Method::Clone,
+    ];
+
+    /// Setting listing the patterns that use this method
+    pub fn key(self) -> &'static str {
+        match self {
+            Method::Copy => "files.copy",
+            Method::Hardlink => "files.hardlink",
+            Method::Symlink => "files.symlink",
+            Method::Clone => "files.clone",
+        }
+    }
+}
+
+/// Patterns and the method for the paths they match
+pub type Rules = Vec<(Method, Vec<String>)>;
+
+/// The `files.*` patterns in effect, skipping methods with none
+pub fn rules(config: &Config) -> Rules {
+    Method::ALL
+        .iter()
+        .filter_map(|method| {
+            let patterns = config::split_list(config.get(method.key())?);
+            Some((*method, patterns))
+        })
+        .collect()
+}
+
+fn method_for(rules: &Rules, rel: &str) -> Option<Method> {
+    rules
+        .iter()
+        .find(|(_, patterns)| patterns.iter().any(|p| glob::matches(p, rel)))
+        .map(|(method, _)| *method)
+}
+
+fn visit(main: &Path, rel: &str, is_dir: bool, rules: &Rules, out: &mut Vec<(String, Method)>) {
+    if let Some(method) = method_for(rules, rel) {
+        out.push((rel.to_string(), method));
+        return;
+    }
+    let worth_walking = rules
+        .iter()
+        .flat_map(|(_, patterns)| patterns)
+        .any(|p| glob::may_match_inside(p, rel));
+    if !is_dir || !worth_walking {
+        return;
+    }
+
+    let mut children: Vec<(String, bool)> = match fs::read_dir(main.join(rel)) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                let is_dir = e.file_type().is_ok_and(|t| t.is_dir());
+                (format!("{}/{}", rel, name), is_dir)
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    children.sort();
+    for (child, is_dir) in children {
+        visit(main, &child, is_dir, rules, out);
+    }
+}
+
+/// Paths to bring over, each with the method of the first rule that matches.
+/// `untracked` is `git ls-files --others --directory` output for `main`:
+/// whole untracked directories end in `/` and are only walked when a
+/// pattern could match something inside. A matching directory is taken as
+/// a whole.
+pub fn find(main: &Path, untracked: &[String], rules: &Rules) -> Vec<(String, Method)> {
+    let mut out = Vec::new();
+    for entry in untracked {
+        let rel = entry.trim_end_matches('/');
+        visit(main, rel, entry.ends_with('/'), rules, &mut out);
+    }
+    out
+}
+
+/// Bytes used by `path` and everything under it, without following symlinks
+fn size_of(path: &Path) -> u64 {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return 0,
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|e| size_of(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Total size of what `apply` would bring over, for an estimate up front
+pub fn estimate(main: &Path, items: &[(String, Method)]) -> u64 {
+    items
+        .iter()
+        .filter(|(_, method)| *method != Method::Symlink)
+        .map(|(rel, _)| size_of(&main.join(rel)))
+        .sum()
+}
+
+/// Shares the data blocks of `src` with a new file `dst` until either is
+/// written to
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let from = fs::File::open(src)?;
+    let to = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)?;
+    // SAFETY: both descriptors are open for the duration of the call
+    if unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) } == -1 {
+        let e = io::Error::last_os_error();
+        drop(to);
+        let _ = fs::remove_file(dst);
+        return Err(e);
+    }
+    to.set_permissions(from.metadata()?.permissions())
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(src.as_os_str().as_bytes())?;
+    let to = CString::new(dst.as_os_str().as_bytes())?;
+    // SAFETY: both are valid NUL-terminated paths
+    if unsafe { libc::clonefile(from.as_ptr(), to.as_ptr(), 0) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Whether a failed reflink means the filesystem can't do it at all, as
+/// opposed to a problem with this one file
+fn reflink_unsupported(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Unsupported
+        || matches!(
+            e.raw_os_error(),
+            Some(libc::EOPNOTSUPP | libc::EXDEV | libc::EINVAL | libc::ENOTTY | libc::ENOSYS)
+        )
+}
+
+/// Copies trees file by file, keeping count of the bytes done
+struct Copier<'a> {
+    /// What `Method::Clone` uses once reflinks turn out not to work
+    fallback: Method,
+    reflink_works: bool,
+    done: u64,
+    progress: &'a mut dyn FnMut(u64),
+}
+
+impl Copier<'_> {
+    /// Copies, hard-links or clones `src` to `dst`, recursing into
+    /// directories and recreating symlinks as they are. Returns the method
+    /// actually used, which for clones may be the fallback.
+    fn copy_tree(&mut self, src: &Path, dst: &Path, method: Method) -> io::Result<Method> {
+        let meta = fs::symlink_metadata(src)?;
+        if meta.file_type().is_symlink() {
+            symlink(fs::read_link(src)?, dst)?;
+            return Ok(method);
+        }
+        if !meta.is_dir() {
+            let used = self.copy_file(src, dst, method)?;
+            self.done += meta.len();
+            (self.progress)(self.done);
+            return Ok(used);
+        }
+
+        fs::create_dir(dst)?;
+        let mut used = method;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            let child = self.copy_tree(&entry.path(), &dst.join(entry.file_name()), method)?;
+            if child != method {
+                used = child;
+            }
+        }
+        Ok(used)
+    }
+
+    fn copy_file(&mut self, src: &Path, dst: &Path, method: Method) -> io::Result<Method> {
+        let method = match method {
+            Method::Clone if self.reflink_works => match reflink(src, dst) {
+                Ok(()) => return Ok(Method::Clone),
+                Err(e) if reflink_unsupported(&e) => {
+                    self.reflink_works = false;
+                    self.fallback
+                }
+                Err(e) => return Err(e),
+            },
+            Method::Clone => self.fallback,
+            other => other,
+        };
+        match method {
+            Method::Hardlink => fs::hard_link(src, dst)?,
+            _ => {
+                fs::copy(src, dst)?;
+            }
+        }
+        Ok(method)
+    }
+}
+
+/// What seeding a new worktree did, by relative path
+#[derive(Debug, Default)]
+pub struct Report {
+    pub copied: Vec<String>,
+    pub hardlinked: Vec<String>,
+    pub symlinked: Vec<String>,
+    /// Copy-on-write clones. Clones that had to fall back count as copied or
+    /// hard-linked.
+    pub cloned: Vec<String>,
+    /// Already present in the new worktree, left alone
+    pub skipped: Vec<String>,
+    /// Path and the error that stopped it
+    pub failed: Vec<(String, String)>,
+}
+
+/// Brings each path from `main` into `target`. Symlinks point back to the
+/// file in `main`, and clones fall back to `fallback` (copy or hard link)
+/// where the filesystem has no copy-on-write. `progress` gets the bytes
+/// done so far after each file.
+pub fn apply(
+    main: &Path,
+    target: &Path,
+    items: &[(String, Method)],
+    fallback: Method,
+    progress: &mut dyn FnMut(u64),
+) -> Report {
+    let mut report = Report::default();
+    let mut copier = Copier {
+        fallback,
+        reflink_works: true,
+        done: 0,
+        progress,
+    };
+
+    for (rel, method) in items {
+        let src = main.join(rel);
+        let dst = target.join(rel);
+        if fs::symlink_metadata(&dst).is_ok() {
+            report.skipped.push(rel.clone());
+            continue;
+        }
+
+        let result = match dst.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|_| match method {
+            Method::Symlink => symlink(&src, &dst).map(|_| Method::Symlink),
+            _ => copier.copy_tree(&src, &dst, *method),
+        });
+
+        match result {
+            Err(e) => report.failed.push((rel.clone(), e.to_string())),
+            Ok(Method::Copy) => report.copied.push(rel.clone()),
+            Ok(Method::Hardlink) => report.hardlinked.push(rel.clone()),
+            Ok(Method::Symlink) => report.symlinked.push(rel.clone()),
+            Ok(Method::Clone) => report.cloned.push(rel.clone()),
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shuu-seed-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rules() -> Rules {
+        vec![
+            (
+                Method::Copy,
+                vec![".env*".to_string(), ".idea/workspace.xml".to_string()],
+            ),
+            (Method::Symlink, vec!["node_modules".to_string()]),
+            (Method::Hardlink, vec!["**/*.db".to_string()]),
+        ]
+    }
+
+    #[test]
+    fn test_find_walks_only_promising_directories() {
+        let main = temp_dir("find");
+        fs::create_dir_all(main.join(".idea")).unwrap();
+        fs::write(main.join(".idea/workspace.xml"), "").unwrap();
+        fs::write(main.join(".idea/other.xml"), "").unwrap();
+        fs::create_dir_all(main.join("data/nested")).unwrap();
+        fs::write(main.join("data/nested/app.db"), "").unwrap();
+
+        let untracked: Vec<String> = [
+            ".env.local",
+            ".idea/",
+            "data/",
+            "node_modules/",
+            "notes.txt",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(
+            find(&main, &untracked, &rules()),
+            vec![
+                (".env.local".to_string(), Method::Copy),
+                (".idea/workspace.xml".to_string(), Method::Copy),
+                ("data/nested/app.db".to_string(), Method::Hardlink),
+                ("node_modules".to_string(), Method::Symlink),
+            ]
+        );
+        let _ = fs::remove_dir_all(&main);
+    }
+
+    #[test]
+    fn test_apply_methods_and_skips() {
+        let main = temp_dir("apply-main");
+        let target = temp_dir("apply-target");
+        fs::write(main.join(".env"), "A=1").unwrap();
+        fs::write(main.join("app.db"), "db").unwrap();
+        fs::create_dir_all(main.join("node_modules/x")).unwrap();
+        fs::write(main.join(".env.local"), "main").unwrap();
+        fs::write(target.join(".env.local"), "mine").unwrap();
+
+        let items = vec![
+            (".env".to_string(), Method::Copy),
+            ("app.db".to_string(), Method::Hardlink),
+            ("node_modules".to_string(), Method::Symlink),
+            (".env.local".to_string(), Method::Copy),
+        ];
+        let report = apply(&main, &target, &items, Method::Copy, &mut |_| {});
+
+        assert_eq!(report.copied, vec![".env"]);
+        assert_eq!(report.hardlinked, vec!["app.db"]);
+        assert_eq!(report.symlinked, vec!["node_modules"]);
+        assert_eq!(report.skipped, vec![".env.local"]);
+        assert!(report.failed.is_empty());
+
+        assert_eq!(fs::read_to_string(target.join(".env")).unwrap(), "A=1");
+        assert_eq!(
+            fs::metadata(target.join("app.db")).unwrap().ino(),
+            fs::metadata(main.join("app.db")).unwrap().ino()
+        );
+        assert_eq!(
+            fs::read_link(target.join("node_modules")).unwrap(),
+            main.join("node_modules")
+        );
+        assert_eq!(
+            fs::read_to_string(target.join(".env.local")).unwrap(),
+            "mine"
+        );
+
+        let _ = fs::remove_dir_all(&main);
+        let _ = fs::remove_dir_all(&target);
+    }
+
+    #[test]
+    fn test_clone_falls_back_and_reports_progress() {
+        let main = temp_dir("clone-main");
+        let target = temp_dir("clone-target");
+        fs::create_dir_all(main.join("target/debug")).unwrap();
+        fs::write(main.join("target/debug/app"), vec![0u8; 3000]).unwrap();
+        fs::write(main.join("target/.rustc_info.json"), "{}").unwrap();
+        symlink("debug/app", main.join("target/latest")).unwrap();
+
+        let items = vec![("target".to_string(), Method::Clone)];
+        let total = estimate(&main, &items);
+        assert_eq!(total, 3002 + "debug/app".len() as u64);
+
+        let mut seen = Vec::new();
+        let mut copier = Copier {
+            fallback: Method::Hardlink,
+            reflink_works: false,
+            done: 0,
+            progress: &mut |done| seen.push(done),
+        };
+        let used = copier
+            .copy_tree(&main.join("target"), &target.join("target"), Method::Clone)
+            .unwrap();
+        assert_eq!(used, Method::Hardlink);
+        assert_eq!(seen.len(), 2);
+        assert_eq!(*seen.last().unwrap(), 3002);
+        assert_eq!(
+            fs::metadata(target.join("target/debug/app")).unwrap().ino(),
+            fs::metadata(main.join("target/debug/app")).unwrap().ino()
+        );
+        assert_eq!(
+            fs::read_link(target.join("target/latest")).unwrap(),
+            Path::new("debug/app")
+        );
+
+        let _ = fs::remove_dir_all(&main);
+        let _ = fs::remove_dir_all(&target);
+    }
+
+    #[test]
+    fn test_clone_without_reflink_copies() {
+        let main = temp_dir("clone-copy-main");
+        let target = temp_dir("clone-copy-target");
+        fs::write(main.join("cache.bin"), "data").unwrap();
+
+        let items = vec![("cache.bin".to_string(), Method::Clone)];
+        let report = apply(&main, &target, &items, Method::Copy, &mut |_| {});
+        // Cloned where the filesystem supports it, copied otherwise
+        assert_eq!(report.cloned.len() + report.copied.len(), 1);
+        assert_eq!(
+            fs::read_to_string(target.join("cache.bin")).unwrap(),
+            "data"
+        );
+
+        let _ = fs::remove_dir_all(&main);
+        let _ = fs::remove_dir_all(&target);
+    }
+
+    #[test]
+    fn test_reflink_unsupported() {
+        assert!(reflink_unsupported(&io::Error::from_raw_os_error(
+            libc::EXDEV
+        )));
+        assert!(reflink_unsupported(&io::ErrorKind::Unsupported.into()));
+        assert!(!reflink_unsupported(&io::Error::from_raw_os_error(
+            libc::ENOSPC
+        )));
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
+
+/// Environment variable through which the shell wrapper receives the
+/// directory to `cd` into once `shuu` exits. `-` prints it on stdout instead.
+pub const CD_FILE_ENV: &str = "SHUU_CD_FILE";
+
+const POSIX_WRAPPER: &str = r#"shuu() {
+  local shuu_cd_file shuu_cd_target shuu_status
+  shuu_cd_file="$(mktemp "${TMPDIR:-/tmp}/shuu.XXXXXX")" || return 1
+  SHUU_CD_FILE="$shuu_cd_file" command shuu "$@"
+  shuu_status=$?
+  shuu_cd_target="$(cat -- "$shuu_cd_file")"
+  rm -f -- "$shuu_cd_file"
+  if [ -n "$shuu_cd_target" ]; then
+    cd -- "$shuu_cd_target" || shuu_status=$?
+  fi
+  return $shuu_status
+}
+"#;
+
+const FISH_WRAPPER: &str = r#"function shuu --wraps shuu
+    set -l shuu_cd_file (mktemp); or return 1
+    SHUU_CD_FILE=$shuu_cd_file command shuu $argv
+    set -l shuu_status $status
+    set -l shuu_cd_target (cat $shuu_cd_file)
+    rm -f $shuu_cd_file
+    if test -n "$shuu_cd_target"
+        cd $shuu_cd_target; or set shuu_status $status
+    end
+    return $shuu_status
+end
+"#;
+
+/// Returns the wrapper function for `shell`, or None if it is not supported.
+pub fn init_script(shell: &str) -> Option<&'static str> {
+    match shell {
+        "zsh" | "bash" => Some(POSIX_WRAPPER),
+        "fish" => Some(FISH_WRAPPER),
+        _ => None,
+    }
+}
+
+/// True if `SHUU_CD_FILE=-` asks for the directory on stdout
+pub fn hands_off_on_stdout() -> bool {
+    std::env::var_os(CD_FILE_ENV).is_some_and(|t| t == "-")
+}
+
+/// Hands `path` back to the calling shell.
+/// Returns Ok(false) if shuu is not running under `shuu init` integration.
+pub fn hand_off(path: &str) -> io::Result<bool> {
+    let target = match std::env::var_os(CD_FILE_ENV) {
+        Some(t) if !t.is_empty() => t,
+        _ => return Ok(false),
+    };
+
+    if hands_off_on_stdout() {
+        println!("{}", path);
+        return Ok(true);
+    }
+
+    let mut file = open_private(Path::new(&target))?;
+    file.write_all(path.as_bytes())?;
+    Ok(true)
+}
+
+/// Opens the handoff file without following symlinks, creating it as 0600
+/// if missing. Refuses anything that is not a regular file owned by us and
+/// inaccessible to other users.
+fn open_private(path: &Path) -> io::Result<fs::File> {
+    let flags = libc::O_NOFOLLOW | libc::O_NONBLOCK;
+    let file = match OpenOptions::new()
+        .write(true)
+        .custom_flags(flags)
+        .open(path)
+    {
+        Ok(f) => f,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .custom_flags(flags)
+            .open(path)?,
+        Err(e) => return Err(e),
+    };
+
+    let meta = file.metadata()?;
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    if !meta.file_type().is_file() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a private file", path.display()),
+        ));
+    }
+
+    file.set_len(0)?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const SHELLS: &[&str] = &["zsh", "bash", "fish"];
+
+    #[test]
+    fn test_init_script_supported_shells() {
+        for shell in SHELLS {
+            let script = init_script(shell).unwrap();
+            assert!(script.contains("SHUU_CD_FILE"));
+            assert!(script.contains("command shuu"));
+        }
+    }
+
+    #[test]
+    fn test_init_script_uses_private_temp_file() {
+        for shell in SHELLS {
+            let script = init_script(shell).unwrap();
+            assert!(script.contains("mktemp"));
+            assert!(!script.contains(".shuu_cd_target"));
+        }
+    }
+
+    #[test]
+    fn test_init_script_deletes_file_before_cd() {
+        for shell in SHELLS {
+            let script = init_script(shell).unwrap();
+            assert!(script.find("rm -f").unwrap() < script.find("cd ").unwrap());
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("shuu-shell-test-{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_open_private_creates_with_0600() {
+        let path = temp_path("new");
+        open_private(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_open_private_truncates_existing() {
+        let path = temp_path("existing");
+        open_private(&path)
+            .unwrap()
+            .write_all(b"/old/long/path")
+            .unwrap();
+        open_private(&path).unwrap().write_all(b"/new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "/new");
+    }
+
+    #[test]
+    fn test_open_private_rejects_shared_file() {
+        let path = temp_path("shared");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(open_private(&path).is_err());
+    }
+
+    #[test]
+    fn test_open_private_rejects_symlink() {
+        let target = temp_path("symlink-target");
+        open_private(&target).unwrap();
+        let link = temp_path("symlink");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert!(open_private(&link).is_err());
+    }
+
+    #[test]
+    fn test_init_script_unknown_shell() {
+        assert!(init_script("powershell").is_none());
+        assert!(init_script("").is_none());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::git;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Worktree that `shuu switch -` goes back to
+const PREVIOUS_FILE: &str = "shuu-previous";
+
+/// `.shuu.toml` hook commands the user approved, one JSON string per line
+const TRUSTED_HOOKS_FILE: &str = "shuu-trusted-hooks";
+
+/// State lives in the repository's git directory, so every repository keeps
+/// its own and removing the repository removes it too
+fn state_file(name: &str) -> Option<PathBuf> {
+    git::common_dir().map(|dir| dir.join(name))
+}
+
+pub fn previous_worktree() -> Option<String> {
+    let content = fs::read_to_string(state_file(PREVIOUS_FILE)?).ok()?;
+    let path = content.trim();
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+pub fn set_previous_worktree(path: &str) -> io::Result<()> {
+    let file = state_file(PREVIOUS_FILE)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in a git repository"))?;
+    fs::write(file, format!("{}\n", path))
+}
+
+/// The full command text, quoted so multi-line commands stay on one line
+fn trusted_entry(command: &str) -> String {
+    serde_json::Value::String(command.to_string()).to_string()
+}
+
+/// Lines that don't parse, such as fingerprints written by older versions,
+/// never match, so those hooks are simply approved again
+fn is_listed(content: &str, command: &str) -> bool {
+    content
+        .lines()
+        .any(|line| serde_json::from_str::<String>(line).is_ok_and(|c| c == command))
+}
+
+/// Whether the user approved exactly this hook command before. Any edit to
+/// the command needs a new approval.
+pub fn hook_is_trusted(command: &str) -> bool {
+    let content = match state_file(TRUSTED_HOOKS_FILE).map(fs::read_to_string) {
+        Some(Ok(c)) => c,
+        _ => return false,
+    };
+    is_listed(&content, command)
+}
+
+pub fn trust_hook(command: &str) -> io::Result<()> {
+    let file = state_file(TRUSTED_HOOKS_FILE)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in a git repository"))?;
+    let mut f = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)?;
+    writeln!(f, "{}", trusted_entry(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trusted_entries() {
+        let multi = "npm install\nnpm run build";
+        let content = format!(
+            "cbf29ce484222325\n{}\n{}\n",
+            trusted_entry("npm install"),
+            trusted_entry(multi)
+        );
+        assert_eq!(content.lines().count(), 3);
+        assert!(is_listed(&content, "npm install"));
+        assert!(is_listed(&content, multi));
+        assert!(!is_listed(&content, "npm  install"));
+        assert!(!is_listed(&content, "npm run build"));
+        assert!(!is_listed(&content, ""));
+    }
+}
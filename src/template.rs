@@ -0,0 +1,186 @@
+use crate::i18n::Messages;
+
+/// Problem with a `{name}` template. `{{` and `}}` stand for literal braces.
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    /// A `{` without its `}`
+    Unclosed,
+    UnknownPlaceholder(String),
+    /// A placeholder the template must use but doesn't
+    MissingPlaceholder(String),
+}
+
+impl TemplateError {
+    pub fn describe(&self, m: &Messages) -> String {
+        match self {
+            TemplateError::Unclosed => m.template_unclosed.to_string(),
+            TemplateError::UnknownPlaceholder(name) => m.template_unknown.replacen("%s", name, 1),
+            TemplateError::MissingPlaceholder(name) => m.template_missing.replacen("%s", name, 1),
+        }
+    }
+}
+
+/// Splits `template` into literal text and placeholder names. A doubled
+/// brace is one literal brace; a lone `}` is kept as written.
+fn parts(template: &str) -> Result<Vec<(bool, &str)>, TemplateError> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        if start > 0 {
+            parts.push((false, &rest[..start]));
+        }
+        let brace = &rest[start..start + 1];
+        let doubled = rest[start + 1..].starts_with(brace);
+        if doubled || brace == "}" {
+            parts.push((false, brace));
+            rest = &rest[start + if doubled { 2 } else { 1 }..];
+            continue;
+        }
+        let end = rest[start..].find('}').ok_or(TemplateError::Unclosed)? + start;
+        parts.push((true, &rest[start + 1..end]));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push((false, rest));
+    }
+
+    Ok(parts)
+}
+
+/// Names of the placeholders in `template`, in order of appearance
+pub fn placeholders(template: &str) -> Result<Vec<&str>, TemplateError> {
+    Ok(parts(template)?
+        .into_iter()
+        .filter(|(is_name, _)| *is_name)
+        .map(|(_, name)| name)
+        .collect())
+}
+
+/// Checks that `template` uses only `known` placeholders, and all of
+/// `required`
+pub fn check(template: &str, known: &[&str], required: &[&str]) -> Result<(), TemplateError> {
+    let names = placeholders(template)?;
+    if let Some(unknown) = names.iter().find(|n| !known.contains(n)) {
+        return Err(TemplateError::UnknownPlaceholder(unknown.to_string()));
+    }
+    match required.iter().find(|r| !names.contains(r)) {
+        Some(missing) => Err(TemplateError::MissingPlaceholder(missing.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// `\t`, `\n` and `\\` as the characters they stand for, since those are
+/// awkward to type in a template given on the command line. Any other
+/// backslash is kept as written.
+pub fn unescape(template: &str) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        let escaped = match (c, chars.peek()) {
+            ('\\', Some('t')) => '\t',
+            ('\\', Some('n')) => '\n',
+            ('\\', Some('\\')) => '\\',
+            _ => {
+                out.push(c);
+                continue;
+            }
+        };
+        chars.next();
+        out.push(escaped);
+    }
+    out
+}
+
+/// Replaces each `{name}` with `value(name)`. Names it returns None for are
+/// errors.
+pub fn render<F>(template: &str, value: F) -> Result<String, TemplateError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::new();
+    for (is_name, text) in parts(template)? {
+        if is_name {
+            let v =
+                value(text).ok_or_else(|| TemplateError::UnknownPlaceholder(text.to_string()))?;
+            out.push_str(&v);
+        } else {
+            out.push_str(text);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "repo" => Some("shuu".to_string()),
+            "branch" => Some("feat/x".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("../{repo}-worktrees/{branch}", vars).unwrap(),
+            "../shuu-worktrees/feat/x"
+        );
+        assert_eq!(render("plain", vars).unwrap(), "plain");
+        assert_eq!(render("{repo}{repo}", vars).unwrap(), "shuushuu");
+    }
+
+    #[test]
+    fn test_render_escaped_braces() {
+        assert_eq!(
+            render("{{\"name\": \"{branch}\"}}", vars).unwrap(),
+            "{\"name\": \"feat/x\"}"
+        );
+        assert_eq!(render("{{{repo}}}", vars).unwrap(), "{shuu}");
+        assert_eq!(render("a}b", vars).unwrap(), "a}b");
+        assert_eq!(placeholders("{{repo}} {branch}").unwrap(), vec!["branch"]);
+    }
+
+    #[test]
+    fn test_render_errors() {
+        assert_eq!(
+            render("{repo}/{nope}", vars),
+            Err(TemplateError::UnknownPlaceholder("nope".to_string()))
+        );
+        assert_eq!(render("{repo", vars), Err(TemplateError::Unclosed));
+    }
+
+    #[test]
+    fn test_check() {
+        let known = ["repo", "branch", "date"];
+        assert_eq!(check("{repo}/{branch}", &known, &["branch"]), Ok(()));
+        assert_eq!(
+            check("{repo}/{date}", &known, &["branch"]),
+            Err(TemplateError::MissingPlaceholder("branch".to_string()))
+        );
+        assert_eq!(
+            check("{branch}/{user}", &known, &["branch"]),
+            Err(TemplateError::UnknownPlaceholder("user".to_string()))
+        );
+        assert_eq!(check("{branch", &known, &[]), Err(TemplateError::Unclosed));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("{path}\\t{branch}\\n"), "{path}\t{branch}\n");
+        assert_eq!(unescape("a\\\\tb"), "a\\tb");
+        assert_eq!(unescape("C:\\dir\\"), "C:\\dir\\");
+    }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            placeholders("~/wt/{repo}/{date}-{branch_slug}").unwrap(),
+            vec!["repo", "date", "branch_slug"]
+        );
+        assert!(placeholders("none").unwrap().is_empty());
+    }
+}